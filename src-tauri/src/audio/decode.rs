@@ -0,0 +1,88 @@
+//! Decoding uploaded audio files (WAV, AIFF, FLAC) to planar f32.
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+pub struct Decoded {
+    pub sample_rate: u32,
+    /// One vec per channel, all the same length.
+    pub planes: Vec<Vec<f32>>,
+}
+
+impl Decoded {
+    pub fn frames(&self) -> usize {
+        self.planes.first().map_or(0, Vec::len)
+    }
+}
+
+/// Decode a whole file held in memory. `extension` is only a hint; the
+/// container is sniffed from the bytes.
+pub fn decode(bytes: Vec<u8>, extension: Option<&str>) -> Result<Decoded, String> {
+    let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes)), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("Unsupported audio file: {e}"))?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| "Audio file has no tracks".to_string())?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| "Audio file has no sample rate".to_string())?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported audio codec: {e}"))?;
+
+    let mut planes: Vec<Vec<f32>> = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(format!("Error reading audio file: {e}")),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            // A corrupt packet costs its samples, not the file.
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Error decoding audio file: {e}")),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count();
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_planar_ref(decoded);
+        let samples = buf.samples();
+        let frames = samples.len() / channels.max(1);
+        if planes.is_empty() {
+            planes = vec![Vec::new(); channels];
+        }
+        for (c, plane) in planes.iter_mut().enumerate() {
+            plane.extend_from_slice(&samples[c * frames..(c + 1) * frames]);
+        }
+    }
+    if planes.is_empty() || planes[0].is_empty() {
+        return Err("Audio file contains no samples".to_string());
+    }
+    Ok(Decoded {
+        sample_rate,
+        planes,
+    })
+}
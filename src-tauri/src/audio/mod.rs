@@ -0,0 +1,120 @@
+//! Audio files for buffer recordings and uploads.
+//!
+//! WAV goes through `hound` as 32-bit float, so a recording is bit-exact
+//! with what scsynth returned. FLAC has no float format; it's written as
+//! 24-bit by the small encoder in `flac`. Uploads are read by `decode`
+//! (symphonia: WAV, AIFF, FLAC) and brought to the server's rate by
+//! `resample`.
+
+pub mod decode;
+pub mod flac;
+pub mod resample;
+
+use flac::FlacWriter;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Wav,
+    Flac,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Wav => "wav",
+            Format::Flac => "flac",
+        }
+    }
+}
+
+enum Writer {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+/// Streaming writer for planar batches (one slice per channel).
+pub struct FileWriter {
+    writer: Writer,
+    channels: usize,
+}
+
+impl FileWriter {
+    pub fn create(
+        path: &Path,
+        format: Format,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        let file = File::create(path)
+            .map(BufWriter::new)
+            .map_err(|e| format!("Error creating {}: {e}", path.display()))?;
+        let writer = match format {
+            Format::Wav => {
+                let spec = hound::WavSpec {
+                    channels,
+                    sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                };
+                Writer::Wav(hound::WavWriter::new(file, spec).map_err(|e| e.to_string())?)
+            }
+            Format::Flac => Writer::Flac(FlacWriter::new(file, channels, sample_rate)?),
+        };
+        Ok(Self {
+            writer,
+            channels: channels as usize,
+        })
+    }
+
+    pub fn write(&mut self, planes: &[Vec<f32>]) -> Result<(), String> {
+        if planes.len() != self.channels {
+            return Err(format!(
+                "expected {} channel(s), got {}",
+                self.channels,
+                planes.len()
+            ));
+        }
+        match &mut self.writer {
+            Writer::Wav(w) => {
+                let frames = planes[0].len();
+                for f in 0..frames {
+                    for plane in planes {
+                        w.write_sample(plane[f]).map_err(|e| e.to_string())?;
+                    }
+                }
+                Ok(())
+            }
+            Writer::Flac(w) => w.write(planes),
+        }
+    }
+
+    /// Flush and patch the length fields in the header.
+    pub fn finalize(self) -> Result<(), String> {
+        match self.writer {
+            Writer::Wav(w) => w.finalize().map_err(|e| e.to_string()),
+            Writer::Flac(w) => w.finalize(),
+        }
+    }
+}
+
+/// A whole 32-bit float WAV file in memory, from interleaved samples (the
+/// layout scsynth buffers use).
+pub fn wav_bytes(interleaved: &[f32], channels: u16, sample_rate: u32) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut out = std::io::Cursor::new(Vec::new());
+    let mut w = hound::WavWriter::new(&mut out, spec).map_err(|e| e.to_string())?;
+    for &s in interleaved {
+        w.write_sample(s).map_err(|e| e.to_string())?;
+    }
+    w.finalize().map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
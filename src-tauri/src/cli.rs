@@ -1,12 +1,19 @@
-use crate::{clock, ipc, plugin, server};
+use crate::{clock, ipc, meter, nrt, pattern, plugin, record, schedule, server, server_info};
 use clap::{Parser, Subcommand};
 use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
 
 #[derive(Parser)]
 #[command(name = "sc-app", about = "SuperCollider plugin dashboard")]
 struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
+
+    /// Scheduling latency for timetagged bundles in GUI mode, in
+    /// milliseconds
+    #[arg(long, default_value_t = schedule::DEFAULT_LATENCY_MS, env = "SC_LATENCY_MS")]
+    latency_ms: u64,
 }
 
 #[derive(Subcommand)]
@@ -20,43 +27,97 @@ enum Command {
         /// scsynth UDP address
         #[arg(long, default_value = "127.0.0.1:57110", env = "SC_SCSYNTH_ADDR")]
         scsynth: String,
+
+        /// Scheduling latency for timetagged bundles, in milliseconds
+        #[arg(long, default_value_t = schedule::DEFAULT_LATENCY_MS, env = "SC_LATENCY_MS")]
+        latency_ms: u64,
     },
 
     /// Manage plugins
     #[command(subcommand)]
     Plugin(plugin::cli::PluginCommand),
+
+    /// Play pattern specs without the GUI
+    #[command(subcommand)]
+    Pattern(pattern::cli::PatternCommand),
+
+    /// Render non-realtime scores
+    #[command(subcommand)]
+    Nrt(nrt::cli::NrtCommand),
+
+    /// Record scsynth buses to a WAV file without the GUI
+    Record(record::cli::RecordArgs),
 }
 
-/// Entry point. Dispatches to GUI, web server, or plugin commands.
+/// Entry point. Dispatches to GUI, web server, plugin, pattern, NRT or
+/// record commands.
 /// Never returns — all branches either block or exit the process.
 pub fn run(context: tauri::Context) -> ! {
     let cli = Cli::parse();
 
     match cli.command {
         None => {
+            let scheduler = Arc::new(schedule::Scheduler::new(Duration::from_millis(
+                cli.latency_ms,
+            )));
             tauri::Builder::default()
                 .plugin(tauri_plugin_opener::init())
                 .plugin(tauri_plugin_fs::init())
                 .manage(ipc::udp::UdpState::new())
                 .manage(ipc::buffer::BufferStreamState::new())
+                .manage(ipc::control::ControlStreamState::new())
+                .manage(ipc::recording::RecordingState::new())
                 .manage(Arc::new(clock::ClockService::new()))
+                .manage(Arc::new(meter::MeterService::new()))
+                .manage(Arc::new(server_info::ServerInfoService::new()))
+                .manage(pattern::PatternState::new(scheduler.clone()))
+                .manage(scheduler)
+                .setup(|app| {
+                    let clock = app.state::<Arc<clock::ClockService>>();
+                    ipc::commands::forward_clock_events(app.handle().clone(), clock.inner());
+                    let meters = app.state::<Arc<meter::MeterService>>();
+                    ipc::commands::forward_meter_events(app.handle().clone(), meters.inner());
+                    Ok(())
+                })
                 .register_uri_scheme_protocol("app", ipc::commands::handle_uri)
                 .invoke_handler(tauri::generate_handler![
                     ipc::commands::udp_bind,
                     ipc::commands::udp_send,
+                    ipc::commands::udp_send_at,
                     ipc::commands::udp_close,
                     ipc::commands::clock_start,
                     ipc::commands::clock_stop,
                     ipc::commands::clock_state,
+                    ipc::commands::server_info_start,
+                    ipc::commands::server_status,
                     ipc::commands::buffer_subscribe,
+                    ipc::commands::buffer_group_subscribe,
                     ipc::commands::buffer_unsubscribe,
+                    ipc::commands::spectrum_subscribe,
+                    ipc::commands::scope_subscribe,
+                    ipc::commands::control_subscribe,
+                    ipc::commands::control_unsubscribe,
+                    ipc::commands::meter_start,
+                    ipc::commands::meter_stop,
+                    ipc::commands::recording_start,
+                    ipc::commands::recording_stop,
+                    ipc::commands::buffer_upload,
+                    ipc::commands::buffer_download,
+                    ipc::commands::clock_upsert,
+                    ipc::commands::pattern_upsert,
+                    ipc::commands::pattern_remove,
+                    ipc::commands::pattern_set_run,
                 ])
                 .run(context)
                 .expect("error while running tauri application");
             std::process::exit(0);
         }
-        Some(Command::Serve { port, scsynth }) => {
-            server::serve(context, port, scsynth);
+        Some(Command::Serve {
+            port,
+            scsynth,
+            latency_ms,
+        }) => {
+            server::serve(context, port, scsynth, Duration::from_millis(latency_ms));
             std::process::exit(0);
         }
         Some(Command::Plugin(cmd)) => {
@@ -68,5 +129,32 @@ pub fn run(context: tauri::Context) -> ! {
                 }
             }
         }
+        Some(Command::Pattern(cmd)) => {
+            match pattern::cli::run(cmd) {
+                Ok(()) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Nrt(cmd)) => {
+            match nrt::cli::run(cmd) {
+                Ok(()) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Record(args)) => {
+            match record::cli::run(args) {
+                Ok(()) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,875 @@
+//! Phase clocks for phase-tracked buffer readers.
+//!
+//! A phase clock is a scsynth-side broadcaster synth running `Phasor.ar` →
+//! `Out.ar` plus `SendTrig.kr` firing `/tr` tagged with the clock's trigger
+//! id at ~10 Hz. The default one (spawned client-side, `__global_clock__`)
+//! writes `PHASE_BUS` and tags with `CLOCK_TRIGGER_ID`; plugins with other
+//! phasor lengths, or other scsynth servers, register their own under a
+//! different name. `ClockService` is the registry; each `PhaseClock` binds a
+//! dedicated UDP socket, registers with `/notify 1`, and fits a line
+//! through the recent /tr stream. Callers query it via `state()` to find
+//! the writer's current virtual sample position — independent of any
+//! particular buffer, since all buffers tracking a clock share its Phasor.
+//!
+//! The fit is a least-squares regression of virtual samples against receipt
+//! time over a sliding window of anchors. Its slope is the soundcard's
+//! actual rate, so extrapolation between /tr packets doesn't saw-tooth the
+//! way a single anchor + nominal rate does when the hardware drifts. UDP
+//! delay only ever makes a /tr arrive *late*, so packets far below the line
+//! are rejected instead of dragging it.
+//!
+//! `/tr` delivery to a `/notify`-ed socket isn't guaranteed across SC
+//! versions (NOTES.md), so a clock can instead poll a control bus the
+//! broadcaster writes the phase to (`Out.kr(bus, A2K.kr(phase))`) with
+//! `/c_get`. The `/c_set` reply is unicast to the asking socket and is used
+//! as the anchor exactly like a `/tr`; `ClockState` means the same either way.
+//!
+//! State transitions (started, anchored, silent, recovered, stopped) plus a
+//! ~1 Hz heartbeat carrying wrap count and fitted rate are published on a
+//! broadcast channel (`ClockService::subscribe()`), so the UI doesn't have
+//! to poll `state()`: GUI mode forwards them as the `clock-state` Tauri
+//! event, serve mode on the `/clock` WebSocket.
+//!
+//! The TS side owns the broadcaster synthdefs and their `/s_new` / `/n_free`
+//! lifecycle in the GUI. A clock can instead own its broadcaster
+//! (`ClockConfig::broadcaster`, always on in serve mode so there's a clock
+//! with no browser open): it's sent from the clock's own socket on
+//! `start()`, freed on `stop()`, and re-created when the server-info poller
+//! restarts the clock after scsynth comes back. An owned broadcaster can
+//! also capture a bus into a buffer off its own Phasor (`Capture`), which
+//! is how `sc-app record` gets a clocked reader with no frontend at all.
+//! See `synth`. Either way each clock is restartable via `start()` which
+//! re-binds and resets.
+
+mod synth;
+
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+
+// Mirror of src/constants/osc.ts — must stay in sync.
+pub const CLOCK_TRIGGER_ID: i32 = 4242;
+pub const SHARED_FRAMES: i64 = 8192;
+pub const PHASE_BUS: i32 = 1000;
+
+/// Name of the shared `__global_clock__` broadcaster's clock.
+pub const DEFAULT_CLOCK: &str = "global";
+
+/// If no `/tr` has arrived for this long after we had an anchor, the
+/// broadcaster is paused (plugin group or default group stopped from UI).
+/// State flips to `Silent`; readers consume it by emitting zeros.
+/// ~3× the broadcaster's 10 Hz tick keeps us robust to scheduler jitter.
+const TR_SILENCE_MS: u64 = 300;
+
+/// Default `/c_get` period for control-bus clocks, matching the `/tr` rate.
+pub const DEFAULT_POLL_MS: u64 = 100;
+
+/// Anchors kept for the fit. At 10 Hz that's ~6 s: long enough to average
+/// out UDP jitter, short enough to follow slow (thermal) drift.
+const FIT_WINDOW: usize = 64;
+/// Below this time span the slope is too noisy to trust; the fit pins the
+/// rate to nominal and only solves for the offset.
+const MIN_FIT_SPAN_S: f64 = 1.0;
+/// Estimated rate is clamped to nominal ± this. Real crystals sit within
+/// ~100 ppm; anything wider means a bad fit, not a bad soundcard.
+const MAX_DRIFT_PPM: f64 = 2000.0;
+/// A /tr is late if it falls below the line by more than this many residual
+/// standard deviations, and never by less than `OUTLIER_FLOOR_MS`.
+const OUTLIER_SIGMAS: f64 = 4.0;
+const OUTLIER_FLOOR_MS: f64 = 2.0;
+/// This many consecutive rejections means the timeline really moved (e.g.
+/// the broadcaster was re-created); start a fresh fit from the new packet.
+const MAX_REJECTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ClockState {
+    /// No `/tr` ever received; broadcaster still warming up (or not started).
+    Waiting,
+    /// Anchor fresh; `samples` is the writer's current virtual sample index,
+    /// read off the fitted line. `drift_ppm` is the fitted rate relative to
+    /// the nominal one; `jitter_ms` is the RMS residual of accepted /tr
+    /// arrivals around the line.
+    #[serde(rename_all = "camelCase")]
+    Running {
+        samples: i64,
+        drift_ppm: f64,
+        jitter_ms: f64,
+    },
+    /// Anchor present but stale — writer is paused.
+    Silent,
+}
+
+/// Sliding-window linear fit `samples = offset + rate * t`, with `t` in
+/// seconds since `epoch` and samples relative to `v0` to keep the
+/// regression well-conditioned.
+struct Fit {
+    epoch: Instant,
+    v0: i64,
+    points: VecDeque<(f64, f64)>,
+    /// Samples per second.
+    rate: f64,
+    offset: f64,
+    /// RMS residual, in samples.
+    jitter: f64,
+    rejects: u32,
+}
+
+impl Fit {
+    fn new(at: Instant, virt: i64, sr: f64) -> Self {
+        let mut points = VecDeque::with_capacity(FIT_WINDOW);
+        points.push_back((0.0, 0.0));
+        Self {
+            epoch: at,
+            v0: virt,
+            points,
+            rate: sr,
+            offset: 0.0,
+            jitter: 0.0,
+            rejects: 0,
+        }
+    }
+
+    /// Add an anchor unless it's a late outlier. Returns whether it was used.
+    fn add(&mut self, at: Instant, virt: i64, sr: f64) -> bool {
+        let t = at.saturating_duration_since(self.epoch).as_secs_f64();
+        let v = (virt - self.v0) as f64;
+        if self.points.len() >= 4 {
+            let residual = v - (self.offset + self.rate * t);
+            let floor = OUTLIER_FLOOR_MS / 1000.0 * self.rate;
+            if residual < -(OUTLIER_SIGMAS * self.jitter).max(floor) {
+                self.rejects += 1;
+                if self.rejects < MAX_REJECTS {
+                    return false;
+                }
+                *self = Fit::new(at, virt, sr);
+                return true;
+            }
+        }
+        self.rejects = 0;
+        if self.points.len() == FIT_WINDOW {
+            self.points.pop_front();
+        }
+        self.points.push_back((t, v));
+        self.refit(sr);
+        true
+    }
+
+    fn refit(&mut self, sr: f64) {
+        let n = self.points.len() as f64;
+        let mean_t = self.points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_v = self.points.iter().map(|p| p.1).sum::<f64>() / n;
+        let span =
+            self.points.back().map_or(0.0, |p| p.0) - self.points.front().map_or(0.0, |p| p.0);
+        let rate = if span >= MIN_FIT_SPAN_S {
+            let (mut stv, mut stt) = (0.0, 0.0);
+            for (t, v) in &self.points {
+                stv += (t - mean_t) * (v - mean_v);
+                stt += (t - mean_t) * (t - mean_t);
+            }
+            let max_dev = sr * MAX_DRIFT_PPM * 1e-6;
+            (stv / stt).clamp(sr - max_dev, sr + max_dev)
+        } else {
+            sr
+        };
+        self.rate = rate;
+        self.offset = mean_v - rate * mean_t;
+        let sq: f64 = self
+            .points
+            .iter()
+            .map(|(t, v)| (v - (self.offset + rate * t)).powi(2))
+            .sum();
+        self.jitter = (sq / n).sqrt();
+    }
+
+    fn samples_at(&self, at: Instant) -> i64 {
+        let t = at.saturating_duration_since(self.epoch).as_secs_f64();
+        self.v0 + (self.offset + self.rate * t) as i64
+    }
+
+    fn instant_at(&self, samples: i64) -> Option<Instant> {
+        let t = ((samples - self.v0) as f64 - self.offset) / self.rate;
+        if t >= 0.0 {
+            Some(self.epoch + Duration::from_secs_f64(t))
+        } else {
+            self.epoch.checked_sub(Duration::from_secs_f64(-t))
+        }
+    }
+
+    fn drift_ppm(&self, sr: f64) -> f64 {
+        (self.rate / sr - 1.0) * 1e6
+    }
+
+    fn jitter_ms(&self) -> f64 {
+        self.jitter / self.rate * 1000.0
+    }
+}
+
+/// Backlog of unread events per subscriber before it starts lagging.
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockTransition {
+    /// Listener (re)bound; state is `Waiting` until the first /tr.
+    Started,
+    /// First /tr since start.
+    Anchored,
+    /// No /tr for `TR_SILENCE_MS` after having an anchor.
+    Silent,
+    /// /tr resumed after a silence; the fit starts over.
+    Recovered,
+    /// ~1 Hz while /tr flows; refreshes wrap count and rate.
+    Heartbeat,
+    Stopped,
+}
+
+/// One entry on `ClockService::subscribe()`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockEvent {
+    pub clock: String,
+    pub transition: ClockTransition,
+    pub state: ClockState,
+    pub wrap_count: i64,
+    /// Fitted soundcard rate in Hz; nominal until the fit has an anchor.
+    pub rate: f64,
+}
+
+/// Where a clock's phase anchors come from.
+#[derive(Debug, Clone, Copy)]
+pub enum AnchorSource {
+    /// `/tr` broadcasts tagged `trigger_id`, received via `/notify 1`.
+    Trigger { trigger_id: i32 },
+    /// `/c_get bus` every `poll_ms`; each `/c_set` reply is an anchor.
+    ControlBus { bus: i32, poll_ms: u64 },
+}
+
+/// A broadcaster synth owned by the clock rather than the frontend.
+#[derive(Debug, Clone, Copy)]
+pub struct Broadcaster {
+    /// Audio bus the Phasor is written to, for phase-tracked writers.
+    /// `None` for a capture synth nothing else follows.
+    pub phase_bus: Option<i32>,
+    /// Also record into a buffer off the same Phasor.
+    pub capture: Option<Capture>,
+}
+
+/// `BufWr` of `channels` audio buses starting at `bus` into `bufnum`,
+/// indexed by the broadcaster's own phase, so a clocked reader of the
+/// buffer follows the writer exactly. The buffer must be `frames` long.
+#[derive(Debug, Clone, Copy)]
+pub struct Capture {
+    pub bufnum: i32,
+    pub bus: i32,
+    pub channels: i32,
+}
+
+/// Everything a `PhaseClock` needs to listen for its broadcaster.
+#[derive(Debug, Clone)]
+pub struct ClockConfig {
+    pub scsynth_addr: String,
+    pub source: AnchorSource,
+    /// Phasor length; the broadcaster's phase wraps at this many samples.
+    pub frames: i64,
+    pub sample_rate: i32,
+    /// `Some` to have the clock create and free the broadcaster itself.
+    pub broadcaster: Option<Broadcaster>,
+}
+
+impl ClockConfig {
+    /// The shared `__global_clock__` broadcaster on `scsynth_addr`.
+    pub fn global(scsynth_addr: &str, sample_rate: i32) -> Self {
+        Self {
+            scsynth_addr: scsynth_addr.to_string(),
+            source: AnchorSource::Trigger {
+                trigger_id: CLOCK_TRIGGER_ID,
+            },
+            frames: SHARED_FRAMES,
+            sample_rate,
+            broadcaster: None,
+        }
+    }
+}
+
+struct Inner {
+    /// Line through recent `/tr` anchors. `None` before the first /tr and
+    /// after a silence (the Phasor stopped, so the old line is void).
+    fit: Option<Fit>,
+    /// Most recent /tr receipt time, for silence detection.
+    last_tr: Option<Instant>,
+    /// Gap after which the clock counts as `Silent`; longer than
+    /// `TR_SILENCE_MS` for slowly polled control-bus clocks.
+    silence_ms: u64,
+    /// Last raw (wrapped) phase, for wrap detection.
+    last_phase: Option<i64>,
+    /// Number of Phasor wraps observed, so `writer_virtual = phase + wrap_count * frames`.
+    wrap_count: i64,
+    /// Nominal sample rate: the fit's starting slope and the reference
+    /// for `drift_ppm`. Set at `start()`.
+    sr: f64,
+}
+
+impl Inner {
+    fn state(&self) -> ClockState {
+        match (&self.fit, self.last_tr) {
+            (None, _) => ClockState::Waiting,
+            (Some(_), Some(ltt)) if ltt.elapsed().as_millis() as u64 > self.silence_ms => {
+                ClockState::Silent
+            }
+            (Some(fit), _) => ClockState::Running {
+                samples: fit.samples_at(Instant::now()),
+                drift_ppm: fit.drift_ppm(self.sr),
+                jitter_ms: fit.jitter_ms(),
+            },
+        }
+    }
+
+    fn event(&self, clock: &str, transition: ClockTransition) -> ClockEvent {
+        ClockEvent {
+            clock: clock.to_string(),
+            transition,
+            state: self.state(),
+            wrap_count: self.wrap_count,
+            rate: self.fit.as_ref().map_or(self.sr, |f| f.rate),
+        }
+    }
+
+    fn reset(&mut self, sr: i32) {
+        self.fit = None;
+        self.last_tr = None;
+        self.last_phase = None;
+        self.wrap_count = 0;
+        self.sr = (sr.max(1)) as f64;
+    }
+}
+
+/// Registry of phase clocks keyed by name.
+pub struct ClockService {
+    clocks: Mutex<HashMap<String, Arc<PhaseClock>>>,
+    events: broadcast::Sender<ClockEvent>,
+}
+
+impl ClockService {
+    pub fn new() -> Self {
+        Self {
+            clocks: Mutex::new(HashMap::new()),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+        }
+    }
+
+    /// State transitions of every clock in the registry.
+    pub fn subscribe(&self) -> broadcast::Receiver<ClockEvent> {
+        self.events.subscribe()
+    }
+
+    /// Handle to the named clock, creating an idle (`Waiting`) one if it
+    /// doesn't exist yet — readers may subscribe before the clock starts.
+    pub async fn clock(&self, id: &str) -> Arc<PhaseClock> {
+        self.clocks
+            .lock()
+            .await
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(PhaseClock::new(id, self.events.clone())))
+            .clone()
+    }
+
+    pub async fn start(&self, id: &str, config: ClockConfig) -> Result<(), String> {
+        self.clock(id).await.start(config).await
+    }
+
+    pub async fn stop(&self, id: &str) {
+        if let Some(c) = self.clocks.lock().await.get(id) {
+            c.stop().await;
+        }
+    }
+
+    pub async fn state(&self, id: &str) -> ClockState {
+        match self.clocks.lock().await.get(id) {
+            Some(c) => c.state().await,
+            None => ClockState::Waiting,
+        }
+    }
+
+    /// scsynth on `scsynth_addr` (re)started at `sample_rate`: restart every
+    /// clock listening there, which re-sends `/notify` and adopts the rate.
+    pub async fn restart_on(&self, scsynth_addr: &str, sample_rate: i32) {
+        let clocks: Vec<Arc<PhaseClock>> = self.clocks.lock().await.values().cloned().collect();
+        for c in clocks {
+            let Some(mut config) = c.config().await else { continue };
+            if config.scsynth_addr != scsynth_addr {
+                continue;
+            }
+            config.sample_rate = sample_rate;
+            if let Err(e) = c.start(config).await {
+                eprintln!("clock[{}] restart failed: {e}", c.id);
+            }
+        }
+    }
+}
+
+pub struct PhaseClock {
+    id: String,
+    inner: Arc<Mutex<Inner>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    /// Config of the running listener; `None` when stopped.
+    config: Mutex<Option<ClockConfig>>,
+    events: broadcast::Sender<ClockEvent>,
+    /// Owned broadcaster's node id and the socket that created it.
+    synth: Mutex<Option<(Arc<UdpSocket>, i32)>>,
+}
+
+impl PhaseClock {
+    fn new(id: &str, events: broadcast::Sender<ClockEvent>) -> Self {
+        Self {
+            id: id.to_string(),
+            inner: Arc::new(Mutex::new(Inner {
+                fit: None,
+                last_tr: None,
+                silence_ms: TR_SILENCE_MS,
+                last_phase: None,
+                wrap_count: 0,
+                sr: 48_000.0,
+            })),
+            task: Mutex::new(None),
+            config: Mutex::new(None),
+            events,
+            synth: Mutex::new(None),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn config(&self) -> Option<ClockConfig> {
+        self.config.lock().await.clone()
+    }
+
+    /// (Re)bind a UDP socket to the configured scsynth, register for
+    /// broadcasts via `/notify 1` (or start polling the control bus), and
+    /// spawn the listener task. If a previous task exists, abort it and
+    /// wipe anchor state first. Safe to call on every connect.
+    pub async fn start(&self, config: ClockConfig) -> Result<(), String> {
+        let ClockConfig {
+            scsynth_addr,
+            source,
+            frames,
+            sample_rate,
+            broadcaster,
+        } = config.clone();
+        let id = self.id.clone();
+        // Wrap detection needs less than one wrap between anchors.
+        let (poll, silence_ms) = match source {
+            AnchorSource::Trigger { .. } => (None, TR_SILENCE_MS),
+            AnchorSource::ControlBus { bus, poll_ms } => {
+                if poll_ms == 0 || poll_ms as i64 * sample_rate as i64 / 1000 >= frames {
+                    return Err(format!(
+                        "clock \"{id}\": poll period {poll_ms} ms must be > 0 and shorter than one {frames}-sample wrap"
+                    ));
+                }
+                let c_get = OscMessage {
+                    addr: "/c_get".into(),
+                    args: vec![OscType::Int(bus)],
+                };
+                let bytes =
+                    encoder::encode(&OscPacket::Message(c_get)).map_err(|e| e.to_string())?;
+                (Some((bytes, poll_ms)), TR_SILENCE_MS.max(3 * poll_ms))
+            }
+        };
+
+        // Free before re-creating: on a first start after the server came
+        // up the old node may still be running. After a real scsynth restart
+        // it's already gone and the `/n_free` just fails server-side.
+        self.free_synth().await;
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+        {
+            let mut g = self.inner.lock().await;
+            g.reset(sample_rate);
+            g.silence_ms = silence_ms;
+        }
+        *self.config.lock().await = None;
+
+        let sock = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| format!("clock bind failed: {e}"))?;
+        sock.connect(&scsynth_addr)
+            .await
+            .map_err(|e| format!("clock connect {scsynth_addr} failed: {e}"))?;
+        let sock = Arc::new(sock);
+
+        match source {
+            AnchorSource::Trigger { trigger_id } => {
+                // Register for /tr broadcasts. `SendTrig` in the broadcaster
+                // synth uses `SendDoneToAllNotified`, so /tr reaches every
+                // notified client.
+                let notify = OscMessage {
+                    addr: "/notify".into(),
+                    args: vec![OscType::Int(1)],
+                };
+                if let Ok(bytes) = encoder::encode(&OscPacket::Message(notify)) {
+                    let _ = sock.send(&bytes).await;
+                }
+                eprintln!(
+                    "clock[{id}] started on {scsynth_addr}; sr={sample_rate} frames={frames}; awaiting /tr id={trigger_id}"
+                );
+            }
+            AnchorSource::ControlBus { bus, poll_ms } => {
+                // `/c_set` replies go back to the sender only; no /notify.
+                eprintln!(
+                    "clock[{id}] started on {scsynth_addr}; sr={sample_rate} frames={frames}; polling /c_get {bus} every {poll_ms}ms"
+                );
+            }
+        }
+
+        // After `/notify`, so the first `/tr` isn't missed.
+        if let Some(b) = broadcaster {
+            let name = synth::def_name(&id);
+            let node_id = synth::next_node_id();
+            let bytes = synth::create(&name, node_id, frames, b, source)?;
+            sock.send(&bytes)
+                .await
+                .map_err(|e| format!("clock broadcaster send failed: {e}"))?;
+            *self.synth.lock().await = Some((sock.clone(), node_id));
+            eprintln!("clock[{id}] created broadcaster {name} as node {node_id}");
+        }
+
+        let _ = self
+            .events
+            .send(self.inner.lock().await.event(&id, ClockTransition::Started));
+
+        let inner = self.inner.clone();
+        let events = self.events.clone();
+        let handle = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            // 10 Hz broadcaster × 10 → ~1 Hz log line and heartbeat event.
+            const TR_LOG_EVERY: u64 = 10;
+            let mut tr_count: u64 = 0;
+            let mut rejected: u64 = 0;
+            // Whether `Silent` was already published for the current gap.
+            let mut silent = false;
+            // Silence is otherwise only noticed lazily by `state()`; this
+            // deadline, pushed back on every anchor, publishes it as it
+            // happens.
+            let silence = Duration::from_millis(silence_ms);
+            let deadline = tokio::time::sleep(silence);
+            tokio::pin!(deadline);
+            let mut poll_interval =
+                tokio::time::interval(Duration::from_millis(poll.as_ref().map_or(1, |p| p.1)));
+            poll_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                let n = tokio::select! {
+                    _ = poll_interval.tick(), if poll.is_some() => {
+                        if let Some((c_get, _)) = &poll {
+                            let _ = sock.send(c_get).await;
+                        }
+                        continue;
+                    }
+                    _ = &mut deadline, if !silent => {
+                        let g = inner.lock().await;
+                        if g.fit.is_some() {
+                            silent = true;
+                            eprintln!("clock[{id}] silent");
+                            let _ = events.send(g.event(&id, ClockTransition::Silent));
+                        } else {
+                            deadline.as_mut().reset(tokio::time::Instant::now() + silence);
+                        }
+                        continue;
+                    }
+                    r = sock.recv(&mut buf) => match r {
+                        Ok(n) => n,
+                        // scsynth down: the poll's ICMP unreachable comes
+                        // back here; keep polling until it answers again.
+                        Err(e) if crate::ipc::udp::transient(&e) => continue,
+                        Err(e) => {
+                            eprintln!("clock[{id}] recv failed: {e}");
+                            break;
+                        }
+                    },
+                };
+                let Ok((_, packet)) = decoder::decode_udp(&buf[..n]) else { continue };
+                let Some(phase) = extract_clock_phase(&packet, source) else { continue };
+                let phase_i = phase as i64;
+
+                let mut g = inner.lock().await;
+                // A polled bus keeps answering with the last value while the
+                // broadcaster is paused; only a moving phase is an anchor.
+                if matches!(source, AnchorSource::ControlBus { .. })
+                    && g.last_phase == Some(phase_i)
+                {
+                    continue;
+                }
+                deadline.as_mut().reset(tokio::time::Instant::now() + silence);
+                let first_anchor = g.fit.is_none();
+                let gap_ms = g.last_tr.map(|t| t.elapsed().as_millis() as u64);
+                let recovering = gap_ms
+                    .map(|ms| ms > g.silence_ms)
+                    .unwrap_or(false);
+                // Wrap detection: Phasor.ar advances monotonically at sample
+                // rate, so any observed backward movement means the Phasor
+                // wrapped. We assume at most one wrap per /tr, which holds as
+                // long as the broadcaster's /tr period advances phase by less
+                // than `frames` samples (10 Hz × 48 kHz = 4800 per /tr ≪ the
+                // default 8192, safe margin).
+                //
+                // The earlier threshold (`phase_i + frames / 2 < lp`)
+                // silently failed here: 4800-sample advance leaves only a
+                // 3392-sample apparent backward jump on wrap, below the
+                // 4096 threshold.
+                if let Some(lp) = g.last_phase {
+                    if phase_i < lp {
+                        g.wrap_count += 1;
+                    }
+                }
+                g.last_phase = Some(phase_i);
+                let virt = phase_i + g.wrap_count * frames;
+                let now = Instant::now();
+                g.last_tr = Some(now);
+                if recovering {
+                    g.fit = None;
+                }
+                let sr = g.sr;
+                let accepted = match g.fit.as_mut() {
+                    Some(fit) => fit.add(now, virt, sr),
+                    None => {
+                        g.fit = Some(Fit::new(now, virt, sr));
+                        true
+                    }
+                };
+                if !accepted {
+                    rejected += 1;
+                }
+                tr_count += 1;
+                if first_anchor {
+                    eprintln!("clock[{id}] anchored; virtual={virt}");
+                    let _ = events.send(g.event(&id, ClockTransition::Anchored));
+                } else if recovering {
+                    eprintln!(
+                        "clock[{id}] recovered from silence (gap={}ms); virtual={virt}",
+                        gap_ms.unwrap_or(0)
+                    );
+                    let _ = events.send(g.event(&id, ClockTransition::Recovered));
+                }
+                silent = false;
+                if tr_count % TR_LOG_EVERY == 0 {
+                    let (ppm, jitter) = g
+                        .fit
+                        .as_ref()
+                        .map_or((0.0, 0.0), |f| (f.drift_ppm(sr), f.jitter_ms()));
+                    eprintln!(
+                        "clock[{id}] heartbeat: count={tr_count} phase={phase_i} virtual={virt} wraps={} drift={ppm:.1}ppm jitter={jitter:.2}ms rejected={rejected}",
+                        g.wrap_count
+                    );
+                    let _ = events.send(g.event(&id, ClockTransition::Heartbeat));
+                }
+            }
+            eprintln!("clock[{id}] listener exited");
+        });
+        *self.task.lock().await = Some(handle);
+        *self.config.lock().await = Some(config);
+        Ok(())
+    }
+
+    /// Abort the listener task and reset anchor state. State becomes `Waiting`.
+    pub async fn stop(&self) {
+        self.free_synth().await;
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+        *self.config.lock().await = None;
+        let mut g = self.inner.lock().await;
+        g.reset(48_000);
+        let _ = self.events.send(g.event(&self.id, ClockTransition::Stopped));
+    }
+
+    /// `/n_free` the owned broadcaster, if any.
+    async fn free_synth(&self) {
+        let Some((sock, node_id)) = self.synth.lock().await.take() else {
+            return;
+        };
+        if let Ok(bytes) = synth::free(node_id) {
+            let _ = sock.send(&bytes).await;
+        }
+        eprintln!("clock[{}] freed broadcaster node {node_id}", self.id);
+    }
+
+    /// Snapshot of clock state. Cheap (one mutex acquisition, no I/O).
+    pub async fn state(&self) -> ClockState {
+        self.inner.lock().await.state()
+    }
+
+    /// Wall-clock instant at which the writer reaches virtual sample
+    /// `samples`, read off the fitted line (backwards too). `None` unless
+    /// the clock is `Running`.
+    pub async fn instant_at(&self, samples: i64) -> Option<Instant> {
+        let g = self.inner.lock().await;
+        let fit = g.fit.as_ref()?;
+        if g.last_tr
+            .is_some_and(|t| t.elapsed().as_millis() as u64 > g.silence_ms)
+        {
+            return None;
+        }
+        fit.instant_at(samples)
+    }
+}
+
+fn extract_clock_phase(packet: &OscPacket, source: AnchorSource) -> Option<f32> {
+    match (packet, source) {
+        (OscPacket::Message(m), AnchorSource::Trigger { trigger_id }) if m.addr == "/tr" => {
+            let mut it = m.args.iter();
+            let _node = it.next()?;
+            let id = match it.next()? {
+                OscType::Int(i) => *i,
+                _ => return None,
+            };
+            if id != trigger_id {
+                return None;
+            }
+            match it.next()? {
+                OscType::Float(f) => Some(*f),
+                _ => None,
+            }
+        }
+        // `/c_set bus value [bus value …]`
+        (OscPacket::Message(m), AnchorSource::ControlBus { bus, .. }) if m.addr == "/c_set" => {
+            m.args.chunks(2).find_map(|pair| match pair {
+                [OscType::Int(i), OscType::Float(f)] if *i == bus => Some(*f),
+                _ => None,
+            })
+        }
+        (OscPacket::Bundle(b), _) => b
+            .content
+            .iter()
+            .find_map(|p| extract_clock_phase(p, source)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f64 = 48_000.0;
+    /// Anchor period at `TRIGGER_HZ`.
+    const TICK: f64 = 0.1;
+
+    /// Deterministic lateness in `[0, max)` seconds per anchor.
+    fn jitter(n: usize, max: f64) -> Vec<f64> {
+        let mut x = 12_345u32;
+        (0..n)
+            .map(|_| {
+                x = x.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (x >> 8) as f64 / (1 << 24) as f64 * max
+            })
+            .collect()
+    }
+
+    /// Anchor `k` of a writer running `ppm` off nominal, arriving `late`
+    /// seconds after it was sent.
+    fn anchor(epoch: Instant, k: usize, ppm: f64, late: f64) -> (Instant, i64) {
+        let t = k as f64 * TICK;
+        let virt = 1_000_000 + (t * SR * (1.0 + ppm * 1e-6)).round() as i64;
+        (epoch + Duration::from_secs_f64(t + late), virt)
+    }
+
+    fn fit(epoch: Instant, ppm: f64, late: &[f64]) -> Fit {
+        let (at, virt) = anchor(epoch, 0, ppm, late[0]);
+        let mut fit = Fit::new(at, virt, SR);
+        for (k, &l) in late.iter().enumerate().skip(1) {
+            let (at, virt) = anchor(epoch, k, ppm, l);
+            assert!(fit.add(at, virt, SR), "anchor {k} rejected");
+        }
+        fit
+    }
+
+    #[test]
+    fn least_squares_recovers_drift() {
+        let epoch = Instant::now();
+        let exact = fit(epoch, 50.0, &[0.0; FIT_WINDOW]);
+        // Only whole-sample rounding left in the anchors.
+        let ppm = exact.drift_ppm(SR);
+        assert!((ppm - 50.0).abs() < 1.0, "{ppm}");
+        assert!(exact.jitter_ms() < 0.01);
+
+        // Uniform lateness over 0.2 ms: an RMS spread of ~0.06 ms, which
+        // over a 6.3 s window leaves a few ppm of slope error.
+        let late = jitter(3 * FIT_WINDOW, 0.0002);
+        let noisy = fit(epoch, -80.0, &late);
+        assert_eq!(noisy.points.len(), FIT_WINDOW);
+        let (ppm, jitter) = (noisy.drift_ppm(SR), noisy.jitter_ms());
+        assert!((ppm + 80.0).abs() < 10.0, "{ppm}");
+        assert!((0.03..0.09).contains(&jitter), "{jitter}");
+
+        // The line maps instants to samples and back.
+        let at = epoch + Duration::from_secs(30);
+        let samples = noisy.samples_at(at);
+        let back = noisy.instant_at(samples).unwrap();
+        let diff = back.max(at) - back.min(at);
+        assert!(diff < Duration::from_micros(50));
+    }
+
+    #[test]
+    fn short_or_wild_fits_are_pinned() {
+        let epoch = Instant::now();
+        // Under MIN_FIT_SPAN_S: nominal rate, offset only.
+        let short = fit(epoch, 300.0, &[0.0; 8]);
+        assert_eq!(short.drift_ppm(SR), 0.0);
+        // Far past any real crystal: clamped.
+        let wild = fit(epoch, 10_000.0, &[0.0; 30]);
+        assert!((wild.drift_ppm(SR) - MAX_DRIFT_PPM).abs() < 1e-6);
+    }
+
+    #[test]
+    fn late_anchors_are_rejected() {
+        let epoch = Instant::now();
+        let late = jitter(40, 0.0005);
+        let mut f = fit(epoch, 20.0, &late);
+        let (rate, offset) = (f.rate, f.offset);
+
+        // 20 ms late is far outside the fit; it's dropped and the line stays.
+        let (at, virt) = anchor(epoch, 40, 20.0, 0.020);
+        assert!(!f.add(at, virt, SR));
+        assert_eq!((f.rate, f.offset), (rate, offset));
+        assert_eq!(f.rejects, 1);
+
+        // Within the 2 ms floor is still fine, and resets the count.
+        let (at, virt) = anchor(epoch, 41, 20.0, 0.0015);
+        assert!(f.add(at, virt, SR));
+        assert_eq!(f.rejects, 0);
+
+        // Early anchors are never outliers: the line was too late, not them.
+        let (at, virt) = anchor(epoch, 42, 20.0, -0.020);
+        assert!(f.add(at, virt, SR));
+    }
+
+    #[test]
+    fn repeated_rejects_start_a_new_fit() {
+        let epoch = Instant::now();
+        let mut f = fit(epoch, 0.0, &[0.0; 40]);
+        // The broadcaster was re-created: its samples start over from 0.
+        let restart = |k: usize| {
+            let t = (40 + k) as f64 * TICK;
+            (
+                epoch + Duration::from_secs_f64(t),
+                (k as f64 * TICK * SR) as i64,
+            )
+        };
+        for k in 0..MAX_REJECTS as usize - 1 {
+            let (at, virt) = restart(k);
+            assert!(!f.add(at, virt, SR));
+        }
+        let (at, virt) = restart(MAX_REJECTS as usize - 1);
+        assert!(f.add(at, virt, SR));
+        assert_eq!((f.points.len(), f.rejects, f.v0), (1, 0, virt));
+        assert_eq!(f.samples_at(at), virt);
+        let (at, virt) = restart(MAX_REJECTS as usize);
+        assert!(f.add(at, virt, SR));
+        assert!((f.samples_at(at) - virt).abs() <= 1);
+    }
+}
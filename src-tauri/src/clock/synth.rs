@@ -0,0 +1,301 @@
+//! Backend-owned phase broadcaster synth.
+//!
+//! The same graph `src/lib/clock/globalClock.ts` compiles, written out as
+//! SCgf v2 bytes here so serve mode has a clock with no browser open:
+//!
+//! ```text
+//! Phasor.ar(0, 1, 0, frames, 0)          → phase
+//! Out.ar(phaseBus, phase)
+//! A2K.kr(phase)                          → pkr
+//! SendTrig.kr(Impulse.kr(10), id, pkr)   (`/tr` anchors)
+//! Out.kr(bus, pkr)                       (control-bus anchors)
+//! BufWr.ar(In.ar(bus, n), bufnum, phase)  (capture)
+//! ```
+//!
+//! It's sent as `/d_recv` with the `/s_new` as completion message, so the
+//! synth starts as soon as the def is loaded, and placed at the head of the
+//! root group — ahead of every client group, so `Out.ar(phaseBus)` lands
+//! before any consumer `BufWr` reads it in the same block. A capture synth
+//! goes at the tail instead: it has to run after whatever writes the bus
+//! it records, and nothing reads its phase bus.
+
+use super::{AnchorSource, Broadcaster};
+use rosc::{encoder, OscMessage, OscPacket, OscType};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Node ids for backend broadcasters, far above the `(clientId + 1) * 1000`
+/// ranges the frontend allocates from.
+const NODE_ID_BASE: i32 = 0x3F00_0000;
+
+static NEXT_NODE_ID: AtomicI32 = AtomicI32::new(NODE_ID_BASE);
+
+/// SCgf calculation rates.
+const CONTROL: u8 = 1;
+const AUDIO: u8 = 2;
+
+/// Rate the broadcaster sends `/tr` at. Plenty for drift correction and
+/// matches the frontend's broadcaster.
+const TRIGGER_HZ: f32 = 10.0;
+
+pub fn next_node_id() -> i32 {
+    NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Synthdef name for clock `clock_id`: `global` → `__global_clock__`.
+pub fn def_name(clock_id: &str) -> String {
+    format!("__{clock_id}_clock__")
+}
+
+/// `/d_recv` of the broadcaster def, completing with an `/s_new` of node
+/// `node_id` at the head (tail, for a capture) of the root group.
+pub fn create(
+    name: &str,
+    node_id: i32,
+    frames: i64,
+    broadcaster: Broadcaster,
+    source: AnchorSource,
+) -> Result<Vec<u8>, String> {
+    let add_action = if broadcaster.capture.is_some() { 1 } else { 0 };
+    let s_new = encode(
+        "/s_new",
+        vec![
+            OscType::String(name.into()),
+            OscType::Int(node_id),
+            OscType::Int(add_action),
+            OscType::Int(0),
+        ],
+    )?;
+    encode(
+        "/d_recv",
+        vec![
+            OscType::Blob(synthdef(name, frames, broadcaster, source)),
+            OscType::Blob(s_new),
+        ],
+    )
+}
+
+pub fn free(node_id: i32) -> Result<Vec<u8>, String> {
+    encode("/n_free", vec![OscType::Int(node_id)])
+}
+
+fn encode(addr: &str, args: Vec<OscType>) -> Result<Vec<u8>, String> {
+    let msg = OscMessage {
+        addr: addr.into(),
+        args,
+    };
+    encoder::encode(&OscPacket::Message(msg)).map_err(|e| e.to_string())
+}
+
+/// Input reference: `(ugen index, output index)`, or `(-1, constant index)`.
+type Input = (i32, i32);
+
+struct UGen {
+    class: &'static str,
+    rate: u8,
+    inputs: Vec<Input>,
+    outputs: usize,
+}
+
+#[derive(Default)]
+struct Graph {
+    constants: Vec<f32>,
+    ugens: Vec<UGen>,
+}
+
+impl Graph {
+    fn konst(&mut self, v: f32) -> Input {
+        let i = match self.constants.iter().position(|c| *c == v) {
+            Some(i) => i,
+            None => {
+                self.constants.push(v);
+                self.constants.len() - 1
+            }
+        };
+        (-1, i as i32)
+    }
+
+    fn ugen(&mut self, class: &'static str, rate: u8, inputs: Vec<Input>, outputs: usize) -> Input {
+        self.ugens.push(UGen {
+            class,
+            rate,
+            inputs,
+            outputs,
+        });
+        (self.ugens.len() as i32 - 1, 0)
+    }
+}
+
+fn synthdef(name: &str, frames: i64, broadcaster: Broadcaster, source: AnchorSource) -> Vec<u8> {
+    let mut g = Graph::default();
+    let zero = g.konst(0.0);
+    let one = g.konst(1.0);
+    let end = g.konst(frames as f32);
+    let phase = g.ugen("Phasor", AUDIO, vec![zero, one, zero, end, zero], 1);
+    if let Some(phase_bus) = broadcaster.phase_bus {
+        let bus = g.konst(phase_bus as f32);
+        g.ugen("Out", AUDIO, vec![bus, phase], 0);
+    }
+    if let Some(c) = broadcaster.capture {
+        let bus = g.konst(c.bus as f32);
+        let channels = c.channels.max(1) as usize;
+        let (input, _) = g.ugen("In", AUDIO, vec![bus], channels);
+        let bufnum = g.konst(c.bufnum as f32);
+        // BufWr.ar(inputs, bufnum, phase, loop: 1)
+        let mut inputs = vec![bufnum, phase, one];
+        inputs.extend((0..channels as i32).map(|ch| (input, ch)));
+        g.ugen("BufWr", AUDIO, inputs, 1);
+    }
+    let pkr = g.ugen("A2K", CONTROL, vec![phase], 1);
+    match source {
+        AnchorSource::Trigger { trigger_id } => {
+            let freq = g.konst(TRIGGER_HZ);
+            let tick = g.ugen("Impulse", CONTROL, vec![freq, zero], 1);
+            let id = g.konst(trigger_id as f32);
+            g.ugen("SendTrig", CONTROL, vec![tick, id, pkr], 0);
+        }
+        AnchorSource::ControlBus { bus, .. } => {
+            let bus = g.konst(bus as f32);
+            g.ugen("Out", CONTROL, vec![bus, pkr], 0);
+        }
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"SCgf");
+    out.extend_from_slice(&2i32.to_be_bytes());
+    out.extend_from_slice(&1i16.to_be_bytes());
+    pstring(&mut out, name);
+    out.extend_from_slice(&(g.constants.len() as i32).to_be_bytes());
+    for c in &g.constants {
+        out.extend_from_slice(&c.to_be_bytes());
+    }
+    // No parameters, no parameter names.
+    out.extend_from_slice(&0i32.to_be_bytes());
+    out.extend_from_slice(&0i32.to_be_bytes());
+    out.extend_from_slice(&(g.ugens.len() as i32).to_be_bytes());
+    for u in &g.ugens {
+        pstring(&mut out, u.class);
+        out.push(u.rate);
+        out.extend_from_slice(&(u.inputs.len() as i32).to_be_bytes());
+        out.extend_from_slice(&(u.outputs as i32).to_be_bytes());
+        out.extend_from_slice(&0i16.to_be_bytes());
+        for (ugen, index) in &u.inputs {
+            out.extend_from_slice(&ugen.to_be_bytes());
+            out.extend_from_slice(&index.to_be_bytes());
+        }
+        out.extend(std::iter::repeat_n(u.rate, u.outputs));
+    }
+    // No variants.
+    out.extend_from_slice(&0i16.to_be_bytes());
+    out
+}
+
+fn pstring(out: &mut Vec<u8>, s: &str) {
+    out.push(s.len() as u8);
+    out.extend_from_slice(s.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Capture;
+
+    #[test]
+    fn trigger_broadcaster_bytes() {
+        let broadcaster = Broadcaster {
+            phase_bus: Some(1000),
+            capture: None,
+        };
+        let source = AnchorSource::Trigger { trigger_id: 4242 };
+        let got = synthdef("__global_clock__", 8192, broadcaster, source);
+        let k = |i: u8| [0xff, 0xff, 0xff, 0xff, 0, 0, 0, i];
+        let u = |i: u8, o: u8| [0, 0, 0, i, 0, 0, 0, o];
+        let want = [
+            b"SCgf\0\0\0\x02\0\x01" as &[u8],
+            b"\x10__global_clock__",
+            // Constants: 0, 1, 8192, 1000, 10, 4242.
+            b"\0\0\0\x06",
+            b"\0\0\0\0\x3f\x80\0\0\x46\0\0\0\x44\x7a\0\0\x41\x20\0\0\x45\x84\x90\0",
+            // No parameters or parameter names; five UGens.
+            b"\0\0\0\0\0\0\0\0\0\0\0\x05",
+            b"\x06Phasor\x02\0\0\0\x05\0\0\0\x01\0\0",
+            &k(0),
+            &k(1),
+            &k(0),
+            &k(2),
+            &k(0),
+            b"\x02",
+            b"\x03Out\x02\0\0\0\x02\0\0\0\0\0\0",
+            &k(3),
+            &u(0, 0),
+            b"\x03A2K\x01\0\0\0\x01\0\0\0\x01\0\0",
+            &u(0, 0),
+            b"\x01",
+            b"\x07Impulse\x01\0\0\0\x02\0\0\0\x01\0\0",
+            &k(4),
+            &k(0),
+            b"\x01",
+            b"\x08SendTrig\x01\0\0\0\x03\0\0\0\0\0\0",
+            &u(3, 0),
+            &k(5),
+            &u(2, 0),
+            // No variants.
+            b"\0\0",
+        ]
+        .concat();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn capture_with_control_bus_anchors() {
+        let broadcaster = Broadcaster {
+            phase_bus: None,
+            capture: Some(Capture {
+                bufnum: 7,
+                bus: 16,
+                channels: 2,
+            }),
+        };
+        let source = AnchorSource::ControlBus {
+            bus: 3,
+            poll_ms: 100,
+        };
+        let got = synthdef("c", 1024, broadcaster, source);
+        let k = |i: u8| [0xff, 0xff, 0xff, 0xff, 0, 0, 0, i];
+        let u = |i: u8, o: u8| [0, 0, 0, i, 0, 0, 0, o];
+        let want = [
+            b"SCgf\0\0\0\x02\0\x01\x01c" as &[u8],
+            // Constants: 0, 1, 1024, 16, 7, 3.
+            b"\0\0\0\x06",
+            b"\0\0\0\0\x3f\x80\0\0\x44\x80\0\0\x41\x80\0\0\x40\xe0\0\0\x40\x40\0\0",
+            b"\0\0\0\0\0\0\0\0\0\0\0\x05",
+            b"\x06Phasor\x02\0\0\0\x05\0\0\0\x01\0\0",
+            &k(0),
+            &k(1),
+            &k(0),
+            &k(2),
+            &k(0),
+            b"\x02",
+            // In.ar(16, 2) feeds both BufWr channel inputs.
+            b"\x02In\x02\0\0\0\x01\0\0\0\x02\0\0",
+            &k(3),
+            b"\x02\x02",
+            b"\x05BufWr\x02\0\0\0\x05\0\0\0\x01\0\0",
+            &k(4),
+            &u(0, 0),
+            &k(1),
+            &u(1, 0),
+            &u(1, 1),
+            b"\x02",
+            b"\x03A2K\x01\0\0\0\x01\0\0\0\x01\0\0",
+            &u(0, 0),
+            b"\x01",
+            // Out.kr(3, pkr) instead of SendTrig.
+            b"\x03Out\x01\0\0\0\x02\0\0\0\0\0\0",
+            &k(5),
+            &u(3, 0),
+            b"\0\0",
+        ]
+        .concat();
+        assert_eq!(got, want);
+    }
+}
@@ -1,10 +1,14 @@
-use crate::clock::{ClockService, ClockState};
+use super::scope::{self, ServerShm};
+use super::trigger::{TriggerConfig, TriggerSink};
+use crate::audio::{FileWriter, Format};
+use crate::clock::{ClockState, PhaseClock};
 use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::ipc::Channel;
+use tauri::ipc::{Channel, InvokeResponseBody};
 use tokio::net::UdpSocket;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
@@ -12,50 +16,441 @@ use tokio_tungstenite::tungstenite::Message;
 
 pub type SubId = u64;
 
+/// Frame header version; bump when the layout below changes.
+pub const FRAME_VERSION: u8 = 1;
+/// Frame header length in bytes.
+pub const FRAME_HEADER_LEN: usize = 24;
+
+/// Samples are missing right before this batch: the transport dropped
+/// batches it couldn't deliver. `dropped` says how many frames.
+pub const FLAG_GAP: u8 = 1;
+/// Zeros stood in for a paused writer (clock silent), not real samples.
+pub const FLAG_SILENT: u8 = 2;
+/// First real batch after a silence; `position` jumps to the writer's.
+pub const FLAG_RESUMED: u8 = 4;
+/// Zeros standing in for a read scsynth never answered, even when asked
+/// again.
+pub const FLAG_FILLED: u8 = 8;
+/// A triggered subscription's free-running window: auto mode found no
+/// trigger in time (`trigger`).
+pub const FLAG_AUTO: u8 = 16;
+/// A `PeakSink` batch: every buffer channel is three planes, min, max and
+/// rms, so `channels` is three times the buffer's.
+pub const FLAG_PEAK: u8 = 32;
+
+/// Where a batch sits in the stream, and what happened just before it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickMeta {
+    /// Virtual frame index of the batch's first frame: the clock's sample
+    /// count in clocked mode, frames since the reader started otherwise.
+    pub position: i64,
+    pub flags: u8,
+    /// Frames lost between the previous batch and this one.
+    pub dropped: u32,
+}
+
+impl TickMeta {
+    /// Fold in the flags and losses of a batch that isn't passed on as is.
+    pub(crate) fn absorb(&mut self, other: TickMeta) {
+        self.flags |= other.flags;
+        self.dropped = self.dropped.saturating_add(other.dropped);
+    }
+}
+
+/// Receives one batch of frames per `/b_setn` reply, de-interleaved: one
+/// equally long slice per buffer channel.
 pub trait BufferSink: Send {
-    fn send(&mut self, tick: &[f32]) -> bool;
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool;
     fn close(&mut self);
 }
 
+/// Encodes batches as the binary frame both live transports send:
+///
+/// ```text
+/// [0]      version   u8 (FRAME_VERSION)
+/// [1]      flags     u8 (FLAG_*)
+/// [2..4]   channels  u16
+/// [4..8]   seq       u32, +1 per batch, delivered or not
+/// [8..16]  position  i64, virtual frame of the first frame
+/// [16..20] frames    u32
+/// [20..24] dropped   u32, frames missing before this batch
+/// [24..]   f32 × frames per channel, channels back to back
+/// ```
+///
+/// All little-endian. A batch the transport can't take still consumes a
+/// sequence number, and its frames are reported as `dropped` (with
+/// `FLAG_GAP`) on the next one that gets through.
+#[derive(Default)]
+struct FrameEncoder {
+    seq: u32,
+    /// Losses not yet reported to the client.
+    carry: TickMeta,
+    /// Header of the batch last encoded, carry included.
+    last: TickMeta,
+}
+
+impl FrameEncoder {
+    fn encode(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> Vec<u8> {
+        let mut meta = meta;
+        meta.absorb(std::mem::take(&mut self.carry));
+        self.last = meta;
+        let frames = tick.first().map_or(0, |c| c.len());
+        let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + frames * tick.len() * 4);
+        buf.push(FRAME_VERSION);
+        buf.push(meta.flags);
+        buf.extend_from_slice(&(tick.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&self.seq.to_le_bytes());
+        buf.extend_from_slice(&meta.position.to_le_bytes());
+        buf.extend_from_slice(&(frames as u32).to_le_bytes());
+        buf.extend_from_slice(&meta.dropped.to_le_bytes());
+        for s in tick.iter().flatten() {
+            buf.extend_from_slice(&s.to_le_bytes());
+        }
+        self.seq = self.seq.wrapping_add(1);
+        buf
+    }
+
+    /// The batch just encoded never reached the client, nor did the losses
+    /// it was reporting.
+    fn lost(&mut self, frames: usize) {
+        self.carry.absorb(self.last);
+        self.carry.flags |= FLAG_GAP;
+        self.carry.dropped = self.carry.dropped.saturating_add(frames as u32);
+    }
+}
+
+/// Sends each batch as a raw binary frame (see `FrameEncoder`), which the
+/// frontend receives as an `ArrayBuffer`.
 pub struct TauriChannelSink {
-    pub channel: Channel<Vec<f32>>,
+    channel: Channel<InvokeResponseBody>,
+    frames: FrameEncoder,
+}
+
+impl TauriChannelSink {
+    pub fn new(channel: Channel<InvokeResponseBody>) -> Self {
+        Self {
+            channel,
+            frames: FrameEncoder::default(),
+        }
+    }
 }
 
 impl BufferSink for TauriChannelSink {
-    fn send(&mut self, tick: &[f32]) -> bool {
-        self.channel.send(tick.to_vec()).is_ok()
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        let frame = self.frames.encode(meta, tick);
+        self.channel.send(InvokeResponseBody::Raw(frame)).is_ok()
     }
     fn close(&mut self) {}
 }
 
+/// Sends each batch as a binary WS message (see `FrameEncoder`). The
+/// outbound queue is short so a slow client loses batches rather than
+/// lagging; the losses show up in the next frame's header.
 pub struct WsSink {
-    pub tx: mpsc::Sender<Message>,
+    tx: mpsc::Sender<Message>,
+    frames: FrameEncoder,
 }
 
-impl BufferSink for WsSink {
-    fn send(&mut self, tick: &[f32]) -> bool {
-        let mut buf = Vec::with_capacity(4 + tick.len() * 4);
-        buf.extend_from_slice(&(tick.len() as u32).to_le_bytes());
-        for s in tick {
-            buf.extend_from_slice(&s.to_le_bytes());
+impl WsSink {
+    pub fn new(tx: mpsc::Sender<Message>) -> Self {
+        Self {
+            tx,
+            frames: FrameEncoder::default(),
         }
-        match self.tx.try_send(Message::Binary(buf.into())) {
+    }
+}
+
+impl BufferSink for WsSink {
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        let frame = self.frames.encode(meta, tick);
+        match self.tx.try_send(Message::Binary(frame.into())) {
             Ok(()) => true,
-            Err(mpsc::error::TrySendError::Full(_)) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.frames.lost(tick.first().map_or(0, |c| c.len()));
+                true
+            }
             Err(mpsc::error::TrySendError::Closed(_)) => false,
         }
     }
     fn close(&mut self) {}
 }
 
+/// What a subscriber receives: every sample, or per-bucket peaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamMode {
+    Raw,
+    Peak,
+}
+
+/// Peak buckets per second when the subscriber doesn't say: a few per
+/// pixel column of a 60 fps waveform.
+pub const DEFAULT_POINTS_PER_SECOND: u32 = 300;
+
+impl StreamMode {
+    /// Wrap `sink` for this mode, and in a `TriggerSink` if `trigger` is
+    /// given; raw untriggered subscribers get it as is. Triggered windows
+    /// aren't contiguous, so they can't be peak-decimated.
+    pub fn wrap(
+        self,
+        sink: Box<dyn BufferSink>,
+        sample_rate: i32,
+        points_per_second: Option<u32>,
+        trigger: Option<TriggerConfig>,
+    ) -> Result<Box<dyn BufferSink>, String> {
+        match (self, trigger) {
+            (StreamMode::Raw, None) => Ok(sink),
+            (StreamMode::Raw, Some(trigger)) => {
+                Ok(Box::new(TriggerSink::new(sink, trigger, sample_rate)?))
+            }
+            (StreamMode::Peak, None) => Ok(Box::new(PeakSink::new(
+                sink,
+                sample_rate,
+                points_per_second.unwrap_or(DEFAULT_POINTS_PER_SECOND),
+            ))),
+            (StreamMode::Peak, Some(_)) => Err("trigger needs raw mode".to_string()),
+        }
+    }
+}
+
+/// Running min / max / sum of squares of one channel's current bucket.
+#[derive(Clone, Copy)]
+struct Bucket {
+    min: f32,
+    max: f32,
+    sum_sq: f64,
+    count: usize,
+}
+
+impl Bucket {
+    const EMPTY: Self = Self {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+        sum_sq: 0.0,
+        count: 0,
+    };
+}
+
+/// Decimates to `(min, max, rms)` per bucket of `sample_rate /
+/// points_per_second` frames before handing on to `inner`. Buckets carry
+/// over between batches, so output doesn't depend on `/b_getn` chunking.
+/// Batches passed on have three planes per channel — min, max, rms —
+/// each one value per completed bucket, `position` the first bucket's
+/// first frame, and `FLAG_PEAK` set; batches completing no bucket aren't
+/// passed on at all.
+pub struct PeakSink {
+    inner: Box<dyn BufferSink>,
+    bucket_frames: usize,
+    buckets: Vec<Bucket>,
+    /// Position of the current bucket's first frame.
+    start: i64,
+    /// Flags and losses of batches folded into the current bucket.
+    carry: TickMeta,
+}
+
+impl PeakSink {
+    pub fn new(inner: Box<dyn BufferSink>, sample_rate: i32, points_per_second: u32) -> Self {
+        let bucket_frames = (sample_rate.max(1) as u32 / points_per_second.max(1)).max(1);
+        Self {
+            inner,
+            bucket_frames: bucket_frames as usize,
+            buckets: Vec::new(),
+            start: 0,
+            carry: TickMeta::default(),
+        }
+    }
+}
+
+impl BufferSink for PeakSink {
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        if self.buckets.len() != tick.len() {
+            self.buckets = vec![Bucket::EMPTY; tick.len()];
+        }
+        if self.buckets[0].count == 0 {
+            self.start = meta.position;
+        }
+        self.carry.absorb(meta);
+        let mut out = vec![Vec::new(); tick.len() * 3];
+        for (ch, plane) in tick.iter().enumerate() {
+            let bucket = &mut self.buckets[ch];
+            for &s in plane {
+                bucket.min = bucket.min.min(s);
+                bucket.max = bucket.max.max(s);
+                bucket.sum_sq += (s as f64) * (s as f64);
+                bucket.count += 1;
+                if bucket.count == self.bucket_frames {
+                    out[ch * 3].push(bucket.min);
+                    out[ch * 3 + 1].push(bucket.max);
+                    out[ch * 3 + 2].push((bucket.sum_sq / bucket.count as f64).sqrt() as f32);
+                    *bucket = Bucket::EMPTY;
+                }
+            }
+        }
+        if out[0].is_empty() {
+            return true;
+        }
+        let mut out_meta = TickMeta {
+            position: self.start,
+            ..std::mem::take(&mut self.carry)
+        };
+        out_meta.flags |= FLAG_PEAK;
+        // The unfinished bucket holds the tail of this batch.
+        let end = meta.position + tick[0].len() as i64;
+        self.start = end - self.buckets[0].count as i64;
+        self.inner.send(out_meta, &out)
+    }
+    fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+/// Writes every batch to an audio file. The header is finalised on
+/// `close()`, or on drop if the reader goes away first.
+pub struct FileSink {
+    writer: Option<FileWriter>,
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn create(
+        path: &Path,
+        format: Format,
+        channels: i32,
+        sample_rate: i32,
+    ) -> Result<Self, String> {
+        let writer = FileWriter::create(path, format, channels as u16, sample_rate as u32)?;
+        Ok(Self {
+            writer: Some(writer),
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn finish(&mut self) {
+        let Some(writer) = self.writer.take() else {
+            return;
+        };
+        match writer.finalize() {
+            Ok(()) => eprintln!("recording[{}] finalised", self.path.display()),
+            Err(e) => eprintln!("recording[{}] finalise failed: {e}", self.path.display()),
+        }
+    }
+}
+
+impl BufferSink for FileSink {
+    fn send(&mut self, _meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        let Some(writer) = self.writer.as_mut() else {
+            return false;
+        };
+        if let Err(e) = writer.write(tick) {
+            eprintln!("recording[{}] write failed: {e}", self.path.display());
+            self.finish();
+            return false;
+        }
+        true
+    }
+    fn close(&mut self) {
+        self.finish();
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Everything that shapes what a reader sends: subscribers share a reader
+/// only when all of it matches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReaderConfig {
+    /// scsynth the buffer lives on; buffer numbers are per server.
+    pub scsynth_addr: String,
+    pub bufnum: i32,
+    pub frames: i32,
+    pub channels: i32,
+    pub chunk: i32,
+    pub sample_rate: i32,
+    /// Phase clock followed in clocked mode; `None` for wall-clock mode.
+    pub clock: Option<String>,
+}
+
+/// A live subscription and the reader it was attached to.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub id: SubId,
+    pub config: ReaderConfig,
+}
+
+/// A reader of several same-shaped buffers in lockstep: every batch holds
+/// the same frames of each, channels back to back in `bufnums` order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupConfig {
+    pub scsynth_addr: String,
+    pub bufnums: Vec<i32>,
+    pub frames: i32,
+    pub channels: i32,
+    pub chunk: i32,
+    pub sample_rate: i32,
+    pub clock: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupSubscription {
+    pub id: SubId,
+    pub config: GroupConfig,
+}
+
+/// A live subscription to a `ScopeOut2` scope buffer of the scsynth on
+/// `port`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopeSubscription {
+    pub id: SubId,
+    pub port: u16,
+    pub index: usize,
+    /// `None` while no `ScopeOut2` has claimed the buffer yet.
+    pub channels: Option<usize>,
+}
+
+/// What a reader reads: a buffer or a group of buffers over `/b_getn`, or
+/// a `ScopeOut2` scope buffer from shared memory (`scope`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReaderKey {
+    Buffer(ReaderConfig),
+    Group(GroupConfig),
+    Scope { port: u16, index: usize },
+}
+
+impl ReaderKey {
+    /// `(frames, channels)` this reader reads `bufnum` on `scsynth_addr`
+    /// as, if it reads it.
+    fn shape_of(&self, scsynth_addr: &str, bufnum: i32) -> Option<(i32, i32)> {
+        match self {
+            ReaderKey::Buffer(c) if c.scsynth_addr == scsynth_addr && c.bufnum == bufnum => {
+                Some((c.frames, c.channels))
+            }
+            ReaderKey::Group(g)
+                if g.scsynth_addr == scsynth_addr && g.bufnums.contains(&bufnum) =>
+            {
+                Some((g.frames, g.channels))
+            }
+            _ => None,
+        }
+    }
+}
+
 struct ReaderHandle {
     task: JoinHandle<()>,
     sinks: Arc<Mutex<HashMap<SubId, Box<dyn BufferSink>>>>,
 }
 
 pub struct BufferStreamState {
-    readers: Mutex<HashMap<i32, ReaderHandle>>,
-    index: Mutex<HashMap<SubId, i32>>,
+    readers: Mutex<HashMap<ReaderKey, ReaderHandle>>,
+    index: Mutex<HashMap<SubId, ReaderKey>>,
     next_id: AtomicU64,
 }
 
@@ -70,49 +465,183 @@ impl BufferStreamState {
 
     /// Subscribe a sink to a buffer's sample stream. Passing `clock = Some(_)`
     /// activates phase-tracked mode: the reader anchors its `/b_getn` target
-    /// to the shared clock's `samples_now()` — appropriate for writers that
-    /// read the shared `PHASE_BUS`. `clock = None` keeps wall-clock mode for
+    /// to that clock's `samples_now()` — appropriate for writers that read
+    /// the clock's phase bus. `clock = None` keeps wall-clock mode for
     /// plain `sc-buffer + RecordBuf` consumers.
+    ///
+    /// `frames` and `chunk` count frames; each is `channels` samples wide.
+    ///
+    /// Subscribers with the same configuration share one reader; any other
+    /// server, chunk, rate or clock gets a reader of its own. `frames` and
+    /// `channels` describe the buffer itself, so a subscription disagreeing
+    /// with a running reader on those is refused — one of the two would be
+    /// streaming garbage.
     pub async fn subscribe(
         &self,
         bufnum: i32,
         frames: i32,
+        channels: i32,
         chunk: i32,
         sample_rate: i32,
         scsynth_addr: &str,
-        clock: Option<Arc<ClockService>>,
+        clock: Option<Arc<PhaseClock>>,
         sink: Box<dyn BufferSink>,
-    ) -> Result<SubId, String> {
+    ) -> Result<Subscription, String> {
+        if channels < 1 {
+            return Err(format!("buffer {bufnum}: channels must be >= 1, got {channels}"));
+        }
+        let config = ReaderConfig {
+            scsynth_addr: scsynth_addr.to_string(),
+            bufnum,
+            frames,
+            channels,
+            chunk,
+            sample_rate,
+            clock: clock.as_ref().map(|c| c.id().to_string()),
+        };
+        let mut readers = self.readers.lock().await;
+        check_shape(&readers, scsynth_addr, bufnum, frames, channels)?;
         let sub_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let key = ReaderKey::Buffer(config.clone());
+        if let Some(h) = readers.get(&key) {
+            h.sinks.lock().await.insert(sub_id, sink);
+        } else {
+            let mut initial = HashMap::new();
+            initial.insert(sub_id, sink);
+            let sinks = Arc::new(Mutex::new(initial));
+            let task = spawn_reader(
+                vec![bufnum],
+                frames,
+                channels,
+                chunk,
+                sample_rate,
+                scsynth_addr.to_string(),
+                clock,
+                sinks.clone(),
+            );
+            readers.insert(key.clone(), ReaderHandle { task, sinks });
+        }
+        drop(readers);
+        self.index.lock().await.insert(sub_id, key);
+        Ok(Subscription { id: sub_id, config })
+    }
+
+    /// Subscribe a sink to several buffers read in lockstep, for stereo
+    /// pairs and multi-scope layouts that need to line up sample for sample.
+    /// One reader issues the same `/b_getn` range to every buffer and only
+    /// passes a batch on once all of them have answered, so each batch
+    /// carries one `position` for all of them: `channels` planes per buffer,
+    /// in `bufnums` order. Arguments are as for `subscribe`; all buffers
+    /// must be `frames` x `channels` and, to be aligned, written off the
+    /// same Phasor — the clock's, in clocked mode.
+    pub async fn subscribe_group(
+        &self,
+        bufnums: Vec<i32>,
+        frames: i32,
+        channels: i32,
+        chunk: i32,
+        sample_rate: i32,
+        scsynth_addr: &str,
+        clock: Option<Arc<PhaseClock>>,
+        sink: Box<dyn BufferSink>,
+    ) -> Result<GroupSubscription, String> {
+        if channels < 1 {
+            return Err(format!("buffer group: channels must be >= 1, got {channels}"));
+        }
+        if bufnums.len() < 2 {
+            return Err("a buffer group needs at least two buffers".to_string());
+        }
+        if let Some(b) = bufnums
+            .iter()
+            .enumerate()
+            .find_map(|(i, b)| bufnums[..i].contains(b).then_some(b))
+        {
+            return Err(format!("buffer {b} is listed twice"));
+        }
+        let config = GroupConfig {
+            scsynth_addr: scsynth_addr.to_string(),
+            bufnums,
+            frames,
+            channels,
+            chunk,
+            sample_rate,
+            clock: clock.as_ref().map(|c| c.id().to_string()),
+        };
         let mut readers = self.readers.lock().await;
-        if let Some(h) = readers.get(&bufnum) {
+        for &bufnum in &config.bufnums {
+            check_shape(&readers, scsynth_addr, bufnum, frames, channels)?;
+        }
+        let sub_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let key = ReaderKey::Group(config.clone());
+        if let Some(h) = readers.get(&key) {
             h.sinks.lock().await.insert(sub_id, sink);
         } else {
             let mut initial = HashMap::new();
             initial.insert(sub_id, sink);
             let sinks = Arc::new(Mutex::new(initial));
             let task = spawn_reader(
-                bufnum,
+                config.bufnums.clone(),
                 frames,
+                channels,
                 chunk,
                 sample_rate,
                 scsynth_addr.to_string(),
                 clock,
                 sinks.clone(),
             );
-            readers.insert(bufnum, ReaderHandle { task, sinks });
+            readers.insert(key.clone(), ReaderHandle { task, sinks });
         }
         drop(readers);
-        self.index.lock().await.insert(sub_id, bufnum);
-        Ok(sub_id)
+        self.index.lock().await.insert(sub_id, key);
+        Ok(GroupSubscription { id: sub_id, config })
+    }
+
+    /// Subscribe a sink to `ScopeOut2` scope buffer `index` of the scsynth
+    /// at `scsynth_addr`, read from its shared memory; see `scope`. Each
+    /// batch is one scope window. Stop with `unsubscribe` like any other.
+    pub async fn subscribe_scope(
+        &self,
+        index: usize,
+        scsynth_addr: &str,
+        sink: Box<dyn BufferSink>,
+    ) -> Result<ScopeSubscription, String> {
+        let port = scope::port_of(scsynth_addr)?;
+        let shm = ServerShm::open(port)?;
+        if index >= shm.num_scopes() {
+            return Err(format!(
+                "scope buffer {index} out of range; scsynth has {}",
+                shm.num_scopes()
+            ));
+        }
+        let channels = shm.channels(index);
+        let key = ReaderKey::Scope { port, index };
+        let sub_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut readers = self.readers.lock().await;
+        if let Some(h) = readers.get(&key) {
+            h.sinks.lock().await.insert(sub_id, sink);
+        } else {
+            let mut initial = HashMap::new();
+            initial.insert(sub_id, sink);
+            let sinks = Arc::new(Mutex::new(initial));
+            let task = scope::spawn_scope_reader(shm, index, sinks.clone());
+            readers.insert(key.clone(), ReaderHandle { task, sinks });
+        }
+        drop(readers);
+        self.index.lock().await.insert(sub_id, key);
+        Ok(ScopeSubscription {
+            id: sub_id,
+            port,
+            index,
+            channels,
+        })
     }
 
     pub async fn unsubscribe(&self, sub_id: SubId) {
-        let Some(bufnum) = self.index.lock().await.remove(&sub_id) else {
+        let Some(key) = self.index.lock().await.remove(&sub_id) else {
             return;
         };
         let mut readers = self.readers.lock().await;
-        let Some(h) = readers.get(&bufnum) else { return };
+        let Some(h) = readers.get(&key) else { return };
         let empty = {
             let mut sinks = h.sinks.lock().await;
             if let Some(mut sink) = sinks.remove(&sub_id) {
@@ -121,13 +650,36 @@ impl BufferStreamState {
             sinks.is_empty()
         };
         if empty {
-            if let Some(h) = readers.remove(&bufnum) {
+            if let Some(h) = readers.remove(&key) {
                 h.task.abort();
             }
         }
     }
 }
 
+/// Refuse to read `bufnum` as `frames` x `channels` if a running reader,
+/// single or grouped, reads it off the same server as something else.
+fn check_shape(
+    readers: &HashMap<ReaderKey, ReaderHandle>,
+    scsynth_addr: &str,
+    bufnum: i32,
+    frames: i32,
+    channels: i32,
+) -> Result<(), String> {
+    match readers
+        .keys()
+        .find_map(|k| {
+            k.shape_of(scsynth_addr, bufnum)
+                .filter(|&shape| shape != (frames, channels))
+        })
+    {
+        Some((f, c)) => Err(format!(
+            "buffer {bufnum} is already streamed as {f} frame(s) x {c} channel(s), not {frames} x {channels}"
+        )),
+        None => Ok(()),
+    }
+}
+
 /// Catch-up reader loop. Each tick we compute a `target` absolute sample
 /// count the reader should have issued by now and fire `/b_getn` until
 /// `samples_issued` catches up. Two modes, selected at subscription:
@@ -144,13 +696,27 @@ impl BufferStreamState {
 ///      single /b_getn returns samples interleaved between two cycles (the
 ///      "seam zone"). Kept for plain `sc-buffer + RecordBuf` consumers that
 ///      don't participate in the shared clock.
+///
+/// All positions and counts are in frames, the unit the clock's Phasor
+/// advances in; `/b_getn` addresses interleaved samples, so requests are
+/// scaled by `channels` and replies de-interleaved before the sinks.
+///
+/// Replies are placed by their start index in a `ReadWindow` and passed on
+/// strictly in read order, so UDP reordering or a duplicate can't scramble
+/// the stream; a read unanswered for `REPLY_TIMEOUT` is asked again once
+/// while the writer hasn't overwritten it yet, then zero-filled.
+///
+/// A group reads each of `bufnums` over the same socket with a window of
+/// its own; every range is issued to all of them at once, and a batch is
+/// released only when all their reads of it are in (`ready_all`).
 fn spawn_reader(
-    bufnum: i32,
+    bufnums: Vec<i32>,
     frames: i32,
+    channels: i32,
     chunk: i32,
     sample_rate: i32,
     addr: String,
-    clock: Option<Arc<ClockService>>,
+    clock: Option<Arc<PhaseClock>>,
     sinks: Arc<Mutex<HashMap<SubId, Box<dyn BufferSink>>>>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
@@ -166,7 +732,7 @@ fn spawn_reader(
             return;
         }
 
-        // Clocked mode listens for /tr on the shared ClockService socket, so
+        // Clocked mode listens for /tr on the PhaseClock's own socket, so
         // the reader socket doesn't need /notify — it only receives /b_setn
         // replies to its own /b_getn (which scsynth unicasts back to the
         // sender regardless of notify state). Wall-clock mode is the same.
@@ -190,6 +756,25 @@ fn spawn_reader(
         // RecordBuf writers have time to fill one cycle before we read.
         const WALLCLOCK_GRACE_MS: u64 = 100;
 
+        let label = format!(
+            "buf {}",
+            bufnums
+                .iter()
+                .map(i32::to_string)
+                .collect::<Vec<_>>()
+                .join("+")
+        );
+        let mut windows: Vec<ReadWindow> = bufnums
+            .iter()
+            .map(|_| ReadWindow::new(channels as usize))
+            .collect();
+        let planes = bufnums.len() * channels as usize;
+        // Position of the next frame sinks expect; silence zeros continue
+        // from here.
+        let mut next_position: i64 = 0;
+        // `FLAG_RESUMED`, held until the next real batch.
+        let mut pending_flags: u8 = 0;
+
         let mut samples_requested: i64 = 0;
         let mut samples_received: i64 = 0;
         let mut reads_issued: u64 = 0;
@@ -199,8 +784,8 @@ fn spawn_reader(
         const HEARTBEAT_EVERY: u64 = 62;
 
         eprintln!(
-            "reader[buf {bufnum}] started; mode={} frames={frames} chunk={chunk} safety={safety_samples}",
-            if clock.is_some() { "clocked" } else { "wallclock" }
+            "reader[{label}] started; mode={} frames={frames} channels={channels} chunk={chunk} safety={safety_samples}",
+            clock.as_ref().map_or("wallclock", |c| c.id())
         );
 
         loop {
@@ -216,30 +801,41 @@ fn spawn_reader(
                             }
                             ClockState::Silent => {
                                 if !was_silent {
-                                    eprintln!("reader[buf {bufnum}] clock silent — injecting zeros");
+                                    eprintln!("reader[{label}] clock silent — injecting zeros");
                                     was_silent = true;
+                                    // Replies still due are from before the
+                                    // pause; the re-snap skips past them.
+                                    windows.iter_mut().for_each(ReadWindow::clear);
                                 }
                                 // Broadcaster paused: push zeros, don't poll
                                 // the stale buffer. Re-snap on next Running.
-                                let zeros = vec![0.0_f32; chunk.max(1) as usize];
+                                let zeros =
+                                    vec![vec![0.0_f32; chunk.max(1) as usize]; planes];
+                                let meta = TickMeta {
+                                    position: next_position,
+                                    flags: FLAG_SILENT,
+                                    dropped: 0,
+                                };
+                                next_position += chunk.max(1) as i64;
                                 let mut guard = sinks.lock().await;
-                                guard.retain(|_, sink| sink.send(&zeros));
+                                guard.retain(|_, sink| sink.send(meta, &zeros));
                                 if guard.is_empty() {
                                     break;
                                 }
                                 first_anchor = true;
                                 continue;
                             }
-                            ClockState::Running { samples: writer_virtual } => {
+                            ClockState::Running { samples: writer_virtual, .. } => {
                                 if was_silent {
-                                    eprintln!("reader[buf {bufnum}] clock resumed");
+                                    eprintln!("reader[{label}] clock resumed");
                                     was_silent = false;
+                                    pending_flags |= FLAG_RESUMED;
                                 }
                                 if first_anchor {
                                     samples_issued = writer_virtual - safety_samples;
                                     first_anchor = false;
                                     eprintln!(
-                                        "reader[buf {bufnum}] clock anchor; virtual={writer_virtual} samples_issued={samples_issued}"
+                                        "reader[{label}] clock anchor; virtual={writer_virtual} samples_issued={samples_issued}"
                                     );
                                 }
                                 writer_virtual - safety_samples
@@ -264,26 +860,42 @@ fn spawn_reader(
                         if delta <= 0 {
                             break;
                         }
-                        let msg = OscMessage {
-                            addr: "/b_getn".into(),
-                            args: vec![
-                                OscType::Int(bufnum),
-                                OscType::Int(pos),
-                                OscType::Int(delta),
-                            ],
-                        };
-                        if let Ok(bytes) = encoder::encode(&OscPacket::Message(msg)) {
-                            let _ = sock.send(&bytes).await;
+                        for (bufnum, window) in bufnums.iter().zip(windows.iter_mut()) {
+                            b_getn(&sock, *bufnum, pos * channels, delta * channels).await;
+                            window.issue(pos * channels, samples_issued, delta, Instant::now());
+                            samples_requested += delta as i64;
+                            reads_issued += 1;
                         }
                         samples_issued += delta as i64;
-                        samples_requested += delta as i64;
-                        reads_issued += 1;
+                    }
+
+                    // The writer is `safety_samples` ahead of what we've
+                    // issued; a read is intact until it laps it.
+                    let writer = samples_issued + safety_samples;
+                    for (bufnum, window) in bufnums.iter().zip(windows.iter_mut()) {
+                        let retries = window.expire(Instant::now(), |r| {
+                            writer < r.position + frames_i64
+                        });
+                        for (start, count) in retries {
+                            b_getn(&sock, *bufnum, start, count * channels).await;
+                        }
+                    }
+                    let ready = ready_all(&mut windows);
+                    if !ready.is_empty() {
+                        if let Some((meta, tick)) = ready.last() {
+                            next_position = meta.position + tick[0].len() as i64;
+                        }
+                        if !deliver(&sinks, ready, &mut pending_flags).await {
+                            break;
+                        }
                     }
 
                     if tick_count % HEARTBEAT_EVERY == 0 {
-                        let in_flight = samples_requested - samples_received;
+                        let st = ReadStats::total(&windows);
+                        let in_flight = samples_requested - samples_received - st.filled_frames;
                         eprintln!(
-                            "reader[buf {bufnum}] heartbeat: requested={samples_requested} received={samples_received} in_flight={in_flight} reads={reads_issued}"
+                            "reader[{label}] heartbeat: requested={samples_requested} received={samples_received} in_flight={in_flight} reads={reads_issued} lost={} filled={} retried={} reordered={} duplicate={} stale={}",
+                            st.filled_frames, st.filled, st.retried, st.reordered, st.duplicate, st.stale
                         );
                     }
                 }
@@ -291,16 +903,23 @@ fn spawn_reader(
                     match r {
                         Ok(n) => {
                             let Ok((_, packet)) = decoder::decode_udp(&buf[..n]) else { continue };
-                            let mut samples = Vec::new();
-                            walk_b_setn(&packet, bufnum, &mut samples);
-                            if !samples.is_empty() {
-                                samples_received += samples.len() as i64;
-                                let mut guard = sinks.lock().await;
-                                guard.retain(|_, sink| sink.send(&samples));
-                                if guard.is_empty() {
-                                    break;
+                            for (bufnum, window) in bufnums.iter().zip(windows.iter_mut()) {
+                                let mut replies = Vec::new();
+                                walk_b_setn(&packet, *bufnum, &mut replies);
+                                for (start, samples) in replies {
+                                    samples_received += window.place(start, &samples) as i64;
                                 }
                             }
+                            let ready = ready_all(&mut windows);
+                            if ready.is_empty() {
+                                continue;
+                            }
+                            if let Some((meta, tick)) = ready.last() {
+                                next_position = meta.position + tick[0].len() as i64;
+                            }
+                            if !deliver(&sinks, ready, &mut pending_flags).await {
+                                break;
+                            }
                         }
                         Err(_) => break,
                     }
@@ -310,7 +929,221 @@ fn spawn_reader(
     })
 }
 
-fn walk_b_setn(packet: &OscPacket, target: i32, out: &mut Vec<f32>) {
+pub(crate) async fn b_getn(sock: &UdpSocket, bufnum: i32, start: i32, count: i32) {
+    let msg = OscMessage {
+        addr: "/b_getn".into(),
+        args: vec![
+            OscType::Int(bufnum),
+            OscType::Int(start),
+            OscType::Int(count),
+        ],
+    };
+    if let Ok(bytes) = encoder::encode(&OscPacket::Message(msg)) {
+        let _ = sock.send(&bytes).await;
+    }
+}
+
+/// Hand batches to every sink, the first carrying `pending_flags`. Returns
+/// whether any sink is left.
+async fn deliver(
+    sinks: &Mutex<HashMap<SubId, Box<dyn BufferSink>>>,
+    batches: Vec<(TickMeta, Vec<Vec<f32>>)>,
+    pending_flags: &mut u8,
+) -> bool {
+    let mut guard = sinks.lock().await;
+    for (mut meta, tick) in batches {
+        meta.flags |= std::mem::take(pending_flags);
+        guard.retain(|_, sink| sink.send(meta, &tick));
+    }
+    !guard.is_empty()
+}
+
+/// How long a `/b_getn` may go unanswered before it's asked again (once)
+/// or zero-filled. Replies normally take well under a millisecond.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Reads tracked before the oldest is given up on: several seconds' worth
+/// at any sensible chunk size.
+const MAX_IN_FLIGHT: usize = 256;
+
+/// An issued `/b_getn`, waiting for its reply.
+struct Read {
+    /// Sample index (`frame * channels`) the read starts at.
+    start: i32,
+    /// Virtual frame of the read's first frame.
+    position: i64,
+    frames: i32,
+    sent_at: Instant,
+    retried: bool,
+    /// De-interleaved reply, or zeros once given up on.
+    data: Option<Vec<Vec<f32>>>,
+    filled: bool,
+}
+
+/// Reply accounting for the heartbeat, in replies unless noted.
+#[derive(Default)]
+struct ReadStats {
+    /// Arrived while an earlier read was still outstanding.
+    reordered: u64,
+    /// Second reply to a read already answered.
+    duplicate: u64,
+    /// Reply to no outstanding read: from before a silence, or arriving
+    /// after its read was zero-filled.
+    stale: u64,
+    /// Reads asked again after `REPLY_TIMEOUT`.
+    retried: u64,
+    /// Reads zero-filled, and their frames.
+    filled: u64,
+    filled_frames: i64,
+}
+
+impl ReadStats {
+    /// Sum over a group's windows.
+    fn total(windows: &[ReadWindow]) -> Self {
+        let mut t = Self::default();
+        for s in windows.iter().map(|w| &w.stats) {
+            t.reordered += s.reordered;
+            t.duplicate += s.duplicate;
+            t.stale += s.stale;
+            t.retried += s.retried;
+            t.filled += s.filled;
+            t.filled_frames += s.filled_frames;
+        }
+        t
+    }
+}
+
+/// Outstanding reads in issue order. Replies are placed by start index and
+/// released from the front only once complete, so sinks always see reads
+/// in the order they were issued.
+struct ReadWindow {
+    reads: VecDeque<Read>,
+    channels: usize,
+    stats: ReadStats,
+}
+
+impl ReadWindow {
+    fn new(channels: usize) -> Self {
+        Self {
+            reads: VecDeque::new(),
+            channels,
+            stats: ReadStats::default(),
+        }
+    }
+
+    fn issue(&mut self, start: i32, position: i64, frames: i32, now: Instant) {
+        if self.reads.len() == MAX_IN_FLIGHT {
+            // scsynth isn't answering; give up on the oldest rather than
+            // tracking reads forever.
+            if let Some(read) = self.reads.iter_mut().find(|r| r.data.is_none()) {
+                Self::fill(read, self.channels, &mut self.stats);
+            }
+        }
+        self.reads.push_back(Read {
+            start,
+            position,
+            frames,
+            sent_at: now,
+            retried: false,
+            data: None,
+            filled: false,
+        });
+    }
+
+    /// Place a `/b_setn` reply. Returns the frames it contributed.
+    fn place(&mut self, start: i32, samples: &[f32]) -> usize {
+        let frames = samples.len() / self.channels;
+        let Some(i) = self
+            .reads
+            .iter()
+            .position(|r| r.start == start && r.frames as usize == frames)
+        else {
+            self.stats.stale += 1;
+            return 0;
+        };
+        if self.reads[i].data.is_some() {
+            if self.reads[i].filled {
+                self.stats.stale += 1;
+            } else {
+                self.stats.duplicate += 1;
+            }
+            return 0;
+        }
+        if self.reads.range(..i).any(|r| r.data.is_none()) {
+            self.stats.reordered += 1;
+        }
+        self.reads[i].data = Some(deinterleave(samples, self.channels));
+        frames
+    }
+
+    /// Deal with reads unanswered for `REPLY_TIMEOUT`: the first time, if
+    /// `intact` says the buffer still holds them, they're returned as
+    /// `(start, frames)` to ask again; otherwise they're zero-filled.
+    fn expire(&mut self, now: Instant, intact: impl Fn(&Read) -> bool) -> Vec<(i32, i32)> {
+        let mut retries = Vec::new();
+        for read in self.reads.iter_mut() {
+            if read.data.is_some() || now.duration_since(read.sent_at) < REPLY_TIMEOUT {
+                continue;
+            }
+            if !read.retried && intact(read) {
+                read.retried = true;
+                read.sent_at = now;
+                self.stats.retried += 1;
+                retries.push((read.start, read.frames));
+            } else {
+                Self::fill(read, self.channels, &mut self.stats);
+            }
+        }
+        retries
+    }
+
+    fn fill(read: &mut Read, channels: usize, stats: &mut ReadStats) {
+        read.data = Some(vec![vec![0.0; read.frames as usize]; channels]);
+        read.filled = true;
+        stats.filled += 1;
+        stats.filled_frames += read.frames as i64;
+    }
+
+    fn front_ready(&self) -> bool {
+        self.reads.front().is_some_and(|r| r.data.is_some())
+    }
+
+    fn clear(&mut self) {
+        self.reads.clear();
+    }
+}
+
+/// Release the reads complete in every window, in order, channels back to
+/// back. A group's windows are issued the same ranges in the same order,
+/// so their fronts always cover the same frames.
+fn ready_all(windows: &mut [ReadWindow]) -> Vec<(TickMeta, Vec<Vec<f32>>)> {
+    let mut out = Vec::new();
+    while !windows.is_empty() && windows.iter().all(ReadWindow::front_ready) {
+        let mut meta = TickMeta::default();
+        let mut planes = Vec::new();
+        for read in windows.iter_mut().filter_map(|w| w.reads.pop_front()) {
+            meta.position = read.position;
+            if read.filled {
+                meta.flags |= FLAG_FILLED;
+            }
+            planes.extend(read.data.unwrap_or_default());
+        }
+        out.push((meta, planes));
+    }
+    out
+}
+
+/// Split interleaved `[l r l r …]` samples into one vec per channel. A
+/// trailing partial frame is dropped.
+fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let frames = samples.len() / channels;
+    (0..channels)
+        .map(|c| (0..frames).map(|f| samples[f * channels + c]).collect())
+        .collect()
+}
+
+/// Collect `(start, samples)` of every `/b_setn` for `target` in `packet`.
+pub(crate) fn walk_b_setn(packet: &OscPacket, target: i32, out: &mut Vec<(i32, Vec<f32>)>) {
     match packet {
         OscPacket::Message(m) => {
             if m.addr != "/b_setn" {
@@ -321,13 +1154,17 @@ fn walk_b_setn(packet: &OscPacket, target: i32, out: &mut Vec<f32>) {
                 Some(OscType::Int(b)) if *b == target => {}
                 _ => return,
             }
-            it.next(); // start
+            let Some(OscType::Int(start)) = it.next() else {
+                return;
+            };
             it.next(); // count
-            for a in it {
-                if let OscType::Float(f) = a {
-                    out.push(*f);
-                }
-            }
+            let samples = it
+                .filter_map(|a| match a {
+                    OscType::Float(f) => Some(*f),
+                    _ => None,
+                })
+                .collect();
+            out.push((*start, samples));
         }
         OscPacket::Bundle(b) => {
             for p in &b.content {
@@ -336,3 +1173,241 @@ fn walk_b_setn(packet: &OscPacket, target: i32, out: &mut Vec<f32>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The frame `frame.test.ts` parses; keep the two in step.
+    const GAP_FRAME: [u8; 40] = [
+        0x01, 0x05, 0x02, 0x00, // version, GAP | RESUMED, 2 channels
+        0x02, 0x00, 0x00, 0x00, // seq 2
+        0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // position 2^32 + 2
+        0x02, 0x00, 0x00, 0x00, // 2 frames
+        0x04, 0x00, 0x00, 0x00, // 4 dropped
+        0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x80, 0xbf, // 0.5, -1.0
+        0x00, 0x00, 0x80, 0x3e, 0x00, 0x00, 0x00, 0x40, // 0.25, 2.0
+    ];
+
+    #[test]
+    fn frame_header_layout() {
+        let mut enc = FrameEncoder::default();
+        let first = enc.encode(TickMeta::default(), &[vec![0.0; 3]]);
+        assert_eq!(first.len(), FRAME_HEADER_LEN + 12);
+        assert_eq!(&first[..4], [FRAME_VERSION, 0, 1, 0]);
+
+        // The second batch never arrives: its frames and flags ride along
+        // with the third, which still gets seq 2.
+        let lost = TickMeta {
+            position: 3,
+            flags: 0,
+            dropped: 1,
+        };
+        enc.encode(lost, &[vec![0.0; 3], vec![0.0; 3]]);
+        enc.lost(3);
+        let meta = TickMeta {
+            position: (1 << 32) + 2,
+            flags: FLAG_RESUMED,
+            dropped: 0,
+        };
+        let frame = enc.encode(meta, &[vec![0.5, -1.0], vec![0.25, 2.0]]);
+        assert_eq!(frame, GAP_FRAME);
+
+        // Reported once, then cleared.
+        let next = enc.encode(TickMeta::default(), &[vec![]]);
+        assert_eq!(next.len(), FRAME_HEADER_LEN);
+        assert_eq!(next[1], 0);
+        assert_eq!(&next[4..8], 3u32.to_le_bytes());
+        assert_eq!(&next[20..24], [0; 4]);
+    }
+
+    #[test]
+    fn losses_accumulate_until_delivered() {
+        let mut enc = FrameEncoder::default();
+        for dropped in [0, 5] {
+            let meta = TickMeta {
+                position: 0,
+                flags: FLAG_FILLED,
+                dropped,
+            };
+            enc.encode(meta, &[vec![0.0; 10]]);
+            enc.lost(10);
+        }
+        let frame = enc.encode(TickMeta::default(), &[vec![0.0]]);
+        assert_eq!(frame[1], FLAG_GAP | FLAG_FILLED);
+        assert_eq!(&frame[4..8], 2u32.to_le_bytes());
+        assert_eq!(&frame[20..24], 25u32.to_le_bytes());
+    }
+
+    /// Two-channel window with reads of 4 frames at virtual frame `4 * k`.
+    fn window(reads: usize, now: Instant) -> ReadWindow {
+        let mut w = ReadWindow::new(2);
+        for k in 0..reads {
+            w.issue(k as i32 * 8, k as i64 * 4, 4, now);
+        }
+        w
+    }
+
+    fn reply(k: i32) -> Vec<f32> {
+        (0..8).map(|i| (k * 8 + i) as f32).collect()
+    }
+
+    #[test]
+    fn replies_are_released_in_issue_order() {
+        let t0 = Instant::now();
+        let mut w = [window(3, t0)];
+        assert_eq!(w[0].place(8, &reply(1)), 4);
+        assert!(ready_all(&mut w).is_empty());
+        assert_eq!(w[0].stats.reordered, 1);
+        assert_eq!(w[0].place(0, &reply(0)), 4);
+        assert_eq!(w[0].place(0, &reply(0)), 0);
+        assert_eq!(w[0].stats.duplicate, 1);
+        let out = ready_all(&mut w);
+        assert_eq!(out.len(), 2);
+        assert_eq!((out[0].0.position, out[1].0.position), (0, 4));
+        let planes = [vec![8.0, 10.0, 12.0, 14.0], vec![9.0, 11.0, 13.0, 15.0]];
+        assert_eq!(out[1].1, planes);
+        assert_eq!(out[0].0.flags, 0);
+    }
+
+    #[test]
+    fn unanswered_reads_are_retried_once_then_filled() {
+        let t0 = Instant::now();
+        let mut w = [window(2, t0)];
+        w[0].place(8, &reply(1));
+
+        // Not yet overdue.
+        let almost = t0 + REPLY_TIMEOUT - Duration::from_millis(1);
+        assert!(w[0].expire(almost, |_| true).is_empty());
+
+        // Overdue and still in the buffer: asked again, clock restarted.
+        let t1 = t0 + REPLY_TIMEOUT;
+        assert_eq!(w[0].expire(t1, |_| true), [(0, 4)]);
+        assert_eq!(w[0].stats.retried, 1);
+        assert!(w[0].expire(t1 + REPLY_TIMEOUT / 2, |_| true).is_empty());
+
+        // Overdue again: zeros, flagged, and the late reply is stale.
+        assert!(w[0].expire(t1 + REPLY_TIMEOUT, |_| true).is_empty());
+        assert_eq!((w[0].stats.filled, w[0].stats.filled_frames), (1, 4));
+        assert_eq!(w[0].place(0, &reply(0)), 0);
+        assert_eq!(w[0].stats.stale, 1);
+        let out = ready_all(&mut w);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].0.flags, FLAG_FILLED);
+        assert_eq!(out[0].1, [vec![0.0; 4], vec![0.0; 4]]);
+        assert_eq!(out[1].0.flags, 0);
+    }
+
+    #[test]
+    fn overwritten_reads_are_filled_without_a_retry() {
+        let t0 = Instant::now();
+        let mut w = [window(2, t0)];
+        // The writer has lapped the first read but not the second.
+        let retries = w[0].expire(t0 + REPLY_TIMEOUT, |r| r.position > 0);
+        assert_eq!(retries, [(8, 4)]);
+        assert_eq!(w[0].stats.filled, 1);
+        assert_eq!(ready_all(&mut w).len(), 1);
+    }
+
+    #[test]
+    fn in_flight_reads_are_capped() {
+        let t0 = Instant::now();
+        let mut w = [window(MAX_IN_FLIGHT, t0)];
+        w[0].place(8, &reply(1));
+        assert!(ready_all(&mut w).is_empty());
+
+        // One more read gives up on the oldest unanswered one.
+        w[0].issue(MAX_IN_FLIGHT as i32 * 8, MAX_IN_FLIGHT as i64 * 4, 4, t0);
+        assert_eq!(w[0].stats.filled, 1);
+        let out = ready_all(&mut w);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].0.flags, FLAG_FILLED);
+        assert_eq!(w[0].reads.len(), MAX_IN_FLIGHT - 1);
+    }
+
+    #[test]
+    fn group_windows_release_together() {
+        let t0 = Instant::now();
+        let mut w = [window(1, t0), window(1, t0)];
+        w[1].place(0, &reply(5));
+        assert!(ready_all(&mut w).is_empty());
+        w[0].expire(t0 + REPLY_TIMEOUT, |_| false);
+        let out = ready_all(&mut w);
+        assert_eq!(out.len(), 1);
+        // Filled in one window flags the whole group's batch.
+        assert_eq!(out[0].0.flags, FLAG_FILLED);
+        assert_eq!(out[0].1.len(), 4);
+        assert_eq!(out[0].1[2], [40.0, 42.0, 44.0, 46.0]);
+    }
+
+    struct Discard;
+
+    impl BufferSink for Discard {
+        fn send(&mut self, _: TickMeta, _: &[Vec<f32>]) -> bool {
+            true
+        }
+        fn close(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn readers_are_per_server() {
+        let state = BufferStreamState::new();
+        let (a, b) = ("127.0.0.1:9", "127.0.0.2:9");
+        let sub = |addr, frames, channels| {
+            let sink = Box::new(Discard);
+            state.subscribe(0, frames, channels, 64, 48_000, addr, None, sink)
+        };
+        let first = sub(a, 1024, 1).await.unwrap();
+        assert_eq!(first.config.scsynth_addr, a);
+        // Buffer 0 on another server is another buffer.
+        let other = sub(b, 2048, 2).await.unwrap();
+        assert!(sub(a, 2048, 2).await.is_err());
+        let sink = Box::new(Discard);
+        let group = state
+            .subscribe_group(vec![1, 0], 2048, 2, 64, 48_000, b, None, sink)
+            .await
+            .unwrap();
+        assert_eq!(state.readers.lock().await.len(), 3);
+        for id in [first.id, other.id, group.id] {
+            state.unsubscribe(id).await;
+        }
+        assert!(state.readers.lock().await.is_empty());
+    }
+
+    type Batches = Arc<std::sync::Mutex<Vec<(TickMeta, Vec<Vec<f32>>)>>>;
+
+    struct Collect(Batches);
+
+    impl BufferSink for Collect {
+        fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+            self.0.lock().unwrap().push((meta, tick.to_vec()));
+            true
+        }
+        fn close(&mut self) {}
+    }
+
+    #[test]
+    fn peak_batches_are_flagged() {
+        let out = Batches::default();
+        // Two-frame buckets; the second batch completes the pending one.
+        let mut sink = PeakSink::new(Box::new(Collect(out.clone())), 2, 1);
+        let meta = |position| TickMeta {
+            position,
+            flags: FLAG_RESUMED,
+            dropped: 0,
+        };
+        sink.send(meta(0), &[vec![0.5, -1.0, 0.25], vec![0.0; 3]]);
+        sink.send(meta(3), &[vec![0.75], vec![0.0]]);
+        let batches = std::mem::take(&mut *out.lock().unwrap());
+        assert_eq!(batches.len(), 2);
+        for (meta, planes) in &batches {
+            assert_eq!(meta.flags, FLAG_PEAK | FLAG_RESUMED);
+            assert_eq!(planes.len(), 6);
+        }
+        let (first, second) = (&batches[0].1, &batches[1]);
+        assert_eq!((first[0][0], first[1][0]), (-1.0, 0.5));
+        assert!((first[2][0] - 0.625f32.sqrt()).abs() < 1e-6);
+        assert_eq!(second.0.position, 2);
+        assert_eq!((second.1[0][0], second.1[1][0]), (0.25, 0.75));
+    }
+}
@@ -1,10 +1,29 @@
-use super::buffer::{BufferStreamState, SubId, TauriChannelSink};
+use super::buffer::{
+    BufferStreamState, GroupSubscription, ScopeSubscription, StreamMode, SubId, Subscription,
+    TauriChannelSink,
+};
+use super::control::{ControlSpec, ControlStreamState};
+use super::download;
+use super::recording::{RecordingInfo, RecordingSpec, RecordingState};
+use super::spectrum::SpectrumSpec;
+use super::trigger::TriggerConfig;
 use super::udp::UdpState;
-use crate::clock::{ClockService, ClockState};
+use super::upload::{self, UploadInfo, UploadProgress};
+use crate::clock::{
+    AnchorSource, Broadcaster, ClockConfig, ClockService, ClockState, DEFAULT_CLOCK,
+    DEFAULT_POLL_MS, PHASE_BUS,
+};
+use crate::meter::{MeterConfig, MeterService};
+use crate::pattern::{ClockSpec, PatternSpec, PatternState};
 use crate::plugin;
+use crate::schedule::{self, Scheduler};
+use crate::server_info::{ServerInfoService, ServerStatus};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tauri::ipc::Channel;
-use tauri::{Emitter, Manager, State, UriSchemeContext, Window};
+use std::time::Instant;
+use tauri::ipc::{Channel, InvokeResponseBody, Response};
+use tauri::{AppHandle, Emitter, Manager, State, UriSchemeContext, Window};
+use tokio::sync::broadcast;
 
 // --- URI scheme handler (`app://…`) ---
 
@@ -61,50 +80,195 @@ pub async fn udp_send(
     state.send(&target, &data).await
 }
 
+/// Like `udp_send`, but wraps `data` (an encoded OSC message or bundle) in
+/// a `#bundle` timetagged for `at` (ms since the Unix epoch, i.e.
+/// `Date.now()` scale; omitted = now) plus the scheduler latency.
+#[tauri::command]
+pub async fn udp_send_at(
+    target: String,
+    data: Vec<u8>,
+    at: Option<f64>,
+    state: State<'_, UdpState>,
+    scheduler: State<'_, Arc<Scheduler>>,
+) -> Result<usize, String> {
+    let timetag = match at {
+        Some(ms) => scheduler.timetag_unix_ms(ms),
+        None => scheduler.timetag(Instant::now()),
+    };
+    let bundle = schedule::bundle_raw(timetag, &[&data]);
+    state.send(&target, &bundle).await
+}
+
 #[tauri::command]
 pub async fn udp_close(state: State<'_, UdpState>) -> Result<(), String> {
     state.close().await
 }
 
-// --- Global clock lifecycle ---
+// --- Phase clock lifecycle ---
 
+/// Start (or restart) the phase clock `clock_id`, default the global one.
+/// `trigger_id` / `frames` default to the global broadcaster's; passing
+/// `control_bus` anchors on `/c_get` replies every `poll_ms` instead of
+/// `/tr`. With `broadcaster` the clock creates the broadcaster synth itself
+/// and frees it on stop; it writes `phase_bus`, which defaults to
+/// `PHASE_BUS` for the global clock only, since `Out.ar` would sum a second
+/// Phasor into the global one. The `sample_rate` is a fallback; the rate
+/// scsynth reports via `/status` wins once the server-info poller has
+/// heard from it, if that poller is on `scsynth_addr`.
 #[tauri::command]
 pub async fn clock_start(
+    clock_id: Option<String>,
     scsynth_addr: String,
     sample_rate: i32,
+    trigger_id: Option<i32>,
+    frames: Option<i64>,
+    control_bus: Option<i32>,
+    poll_ms: Option<u64>,
+    broadcaster: Option<bool>,
+    phase_bus: Option<i32>,
     state: State<'_, Arc<ClockService>>,
+    server_info: State<'_, Arc<ServerInfoService>>,
 ) -> Result<(), String> {
-    state.start(&scsynth_addr, sample_rate).await
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(sample_rate);
+    let mut config = ClockConfig::global(&scsynth_addr, sample_rate);
+    if let Some(trigger_id) = trigger_id {
+        config.source = AnchorSource::Trigger { trigger_id };
+    }
+    if let Some(bus) = control_bus {
+        config.source = AnchorSource::ControlBus {
+            bus,
+            poll_ms: poll_ms.unwrap_or(DEFAULT_POLL_MS),
+        };
+    }
+    let id = clock_id.as_deref().unwrap_or(DEFAULT_CLOCK);
+    if broadcaster.unwrap_or(false) {
+        let phase_bus = match phase_bus {
+            None if id == DEFAULT_CLOCK => PHASE_BUS,
+            None => return Err(format!("clock {id}: broadcaster needs a phase_bus")),
+            Some(PHASE_BUS) if id != DEFAULT_CLOCK => {
+                return Err(format!(
+                    "clock {id}: phase_bus {PHASE_BUS} belongs to the global clock"
+                ))
+            }
+            Some(bus) => bus,
+        };
+        config.broadcaster = Some(Broadcaster {
+            phase_bus: Some(phase_bus),
+            capture: None,
+        });
+    }
+    if let Some(n) = frames {
+        if n <= 0 {
+            return Err(format!("clock frames must be > 0, got {n}"));
+        }
+        config.frames = n;
+    }
+    state.start(id, config).await
+}
+
+/// Re-emit every `ClockService` transition as a `clock-state` event, so
+/// the UI can listen instead of polling `clock_state`.
+pub fn forward_clock_events(app: AppHandle, clock: &ClockService) {
+    let mut rx = clock.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(ev) => {
+                    let _ = app.emit("clock-state", ev);
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 }
 
 #[tauri::command]
-pub async fn clock_stop(state: State<'_, Arc<ClockService>>) -> Result<(), String> {
-    state.stop().await;
+pub async fn clock_stop(
+    clock_id: Option<String>,
+    state: State<'_, Arc<ClockService>>,
+) -> Result<(), String> {
+    state
+        .stop(clock_id.as_deref().unwrap_or(DEFAULT_CLOCK))
+        .await;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn clock_state(state: State<'_, Arc<ClockService>>) -> Result<ClockState, String> {
-    Ok(state.state().await)
+pub async fn clock_state(
+    clock_id: Option<String>,
+    state: State<'_, Arc<ClockService>>,
+) -> Result<ClockState, String> {
+    Ok(state
+        .state(clock_id.as_deref().unwrap_or(DEFAULT_CLOCK))
+        .await)
+}
+
+// --- scsynth server info ---
+
+/// Start polling `/status` on `scsynth_addr`. Also restarts the phase
+/// clocks on that server with the reported sample rate on connect and after
+/// restarts.
+#[tauri::command]
+pub async fn server_info_start(
+    scsynth_addr: String,
+    clock: State<'_, Arc<ClockService>>,
+    state: State<'_, Arc<ServerInfoService>>,
+) -> Result<(), String> {
+    state
+        .start(&scsynth_addr, Some(clock.inner().clone()))
+        .await
+}
+
+#[tauri::command]
+pub async fn server_status(
+    state: State<'_, Arc<ServerInfoService>>,
+) -> Result<Option<ServerStatus>, String> {
+    Ok(state.status())
 }
 
 // --- Buffer subscriptions ---
 
+/// `clock_id` picks the phase clock a `phase_tracked` reader follows;
+/// default the global one. `channels` defaults to mono; batches arrive
+/// planar, one `frames`-long run per channel. `trigger` turns a raw stream
+/// into triggered scope windows. Returns the subscription id and the reader
+/// configuration it was attached to.
 #[tauri::command]
 pub async fn buffer_subscribe(
     bufnum: i32,
     frames: i32,
+    channels: Option<i32>,
     chunk: i32,
     sample_rate: i32,
     scsynth_addr: String,
     phase_tracked: bool,
-    channel: Channel<Vec<f32>>,
+    clock_id: Option<String>,
+    mode: Option<StreamMode>,
+    points_per_second: Option<u32>,
+    trigger: Option<TriggerConfig>,
+    channel: Channel<InvokeResponseBody>,
     clock: State<'_, Arc<ClockService>>,
+    server_info: State<'_, Arc<ServerInfoService>>,
     state: State<'_, BufferStreamState>,
-) -> Result<SubId, String> {
-    let sink = Box::new(TauriChannelSink { channel });
+) -> Result<Subscription, String> {
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(sample_rate);
+    let sink = mode.unwrap_or(StreamMode::Raw).wrap(
+        Box::new(TauriChannelSink::new(channel)),
+        sample_rate,
+        points_per_second,
+        trigger,
+    )?;
     let clock_opt = if phase_tracked {
-        Some(clock.inner().clone())
+        Some(
+            clock
+                .clock(clock_id.as_deref().unwrap_or(DEFAULT_CLOCK))
+                .await,
+        )
     } else {
         None
     };
@@ -112,6 +276,61 @@ pub async fn buffer_subscribe(
         .subscribe(
             bufnum,
             frames,
+            channels.unwrap_or(1),
+            chunk,
+            sample_rate,
+            &scsynth_addr,
+            clock_opt,
+            sink,
+        )
+        .await
+}
+
+/// Read `bufnums` in lockstep; each channel message holds the same frames
+/// of every buffer, `channels` planes per buffer in `bufnums` order, under
+/// one position. Otherwise as `buffer_subscribe`; a `trigger` channel
+/// counts across the whole group. Stop with `buffer_unsubscribe`.
+#[tauri::command]
+pub async fn buffer_group_subscribe(
+    bufnums: Vec<i32>,
+    frames: i32,
+    channels: Option<i32>,
+    chunk: i32,
+    sample_rate: i32,
+    scsynth_addr: String,
+    phase_tracked: bool,
+    clock_id: Option<String>,
+    mode: Option<StreamMode>,
+    points_per_second: Option<u32>,
+    trigger: Option<TriggerConfig>,
+    channel: Channel<InvokeResponseBody>,
+    clock: State<'_, Arc<ClockService>>,
+    server_info: State<'_, Arc<ServerInfoService>>,
+    state: State<'_, BufferStreamState>,
+) -> Result<GroupSubscription, String> {
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(sample_rate);
+    let sink = mode.unwrap_or(StreamMode::Raw).wrap(
+        Box::new(TauriChannelSink::new(channel)),
+        sample_rate,
+        points_per_second,
+        trigger,
+    )?;
+    let clock_opt = if phase_tracked {
+        Some(
+            clock
+                .clock(clock_id.as_deref().unwrap_or(DEFAULT_CLOCK))
+                .await,
+        )
+    } else {
+        None
+    };
+    state
+        .subscribe_group(
+            bufnums,
+            frames,
+            channels.unwrap_or(1),
             chunk,
             sample_rate,
             &scsynth_addr,
@@ -129,3 +348,224 @@ pub async fn buffer_unsubscribe(
     state.unsubscribe(sub_id).await;
     Ok(())
 }
+
+/// Stream magnitude spectra of a buffer; each channel message is one
+/// spectrum, channels back to back. Stop with `buffer_unsubscribe`.
+#[tauri::command]
+pub async fn spectrum_subscribe(
+    spec: SpectrumSpec,
+    scsynth_addr: String,
+    channel: Channel<InvokeResponseBody>,
+    clock: State<'_, Arc<ClockService>>,
+    server_info: State<'_, Arc<ServerInfoService>>,
+    state: State<'_, BufferStreamState>,
+) -> Result<Subscription, String> {
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(spec.sample_rate);
+    spec.subscribe(
+        Box::new(TauriChannelSink::new(channel)),
+        &scsynth_addr,
+        sample_rate,
+        &clock,
+        &state,
+    )
+    .await
+}
+
+/// Stream `ScopeOut2` scope buffer `index` from the shared memory of the
+/// scsynth at `scsynth_addr` (same machine only); each channel message is
+/// one scope window. Stop with `buffer_unsubscribe`.
+#[tauri::command]
+pub async fn scope_subscribe(
+    index: usize,
+    scsynth_addr: String,
+    channel: Channel<InvokeResponseBody>,
+    state: State<'_, BufferStreamState>,
+) -> Result<ScopeSubscription, String> {
+    state
+        .subscribe_scope(
+            index,
+            &scsynth_addr,
+            Box::new(TauriChannelSink::new(channel)),
+        )
+        .await
+}
+
+// --- Control subscriptions ---
+
+/// Poll control buses (`{"kind": "bus", "index", "count"}`) or node controls
+/// (`{"kind": "node", "node", "controls": [name or index…]}`) at `rate` Hz;
+/// each channel message is one update, one channel per value, sent only
+/// when something changed.
+#[tauri::command]
+pub async fn control_subscribe(
+    spec: ControlSpec,
+    scsynth_addr: String,
+    channel: Channel<InvokeResponseBody>,
+    state: State<'_, ControlStreamState>,
+) -> Result<SubId, String> {
+    state
+        .subscribe(
+            spec,
+            &scsynth_addr,
+            Box::new(TauriChannelSink::new(channel)),
+        )
+        .await
+}
+
+#[tauri::command]
+pub async fn control_unsubscribe(
+    sub_id: SubId,
+    state: State<'_, ControlStreamState>,
+) -> Result<(), String> {
+    state.unsubscribe(sub_id).await;
+    Ok(())
+}
+
+// --- Level meters ---
+
+/// Listen for `SendPeakRMS` replies from `scsynth_addr`; levels arrive as
+/// `meter-levels` events. `config` defaults to `/reply` with 1.5 s peak hold
+/// and a 24 dB/s fall.
+#[tauri::command]
+pub async fn meter_start(
+    scsynth_addr: String,
+    config: Option<MeterConfig>,
+    state: State<'_, Arc<MeterService>>,
+) -> Result<(), String> {
+    state
+        .start(&scsynth_addr, config.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+pub async fn meter_stop(state: State<'_, Arc<MeterService>>) -> Result<(), String> {
+    state.stop().await;
+    Ok(())
+}
+
+/// Re-emit every meter frame as a `meter-levels` event: an array of
+/// `MeterLevels`, one per meter that changed.
+pub fn forward_meter_events(app: AppHandle, meters: &MeterService) {
+    let mut rx = meters.subscribe(None);
+    tauri::async_runtime::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let _ = app.emit("meter-levels", frame);
+        }
+    });
+}
+
+// --- Buffer recordings ---
+
+/// Record a buffer stream to `<app data>/recordings/`.
+#[tauri::command]
+pub async fn recording_start(
+    app: AppHandle,
+    spec: RecordingSpec,
+    scsynth_addr: String,
+    clock: State<'_, Arc<ClockService>>,
+    server_info: State<'_, Arc<ServerInfoService>>,
+    streams: State<'_, BufferStreamState>,
+    state: State<'_, RecordingState>,
+) -> Result<RecordingInfo, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(spec.sample_rate);
+    state
+        .start(
+            spec,
+            &data_dir,
+            &scsynth_addr,
+            sample_rate,
+            &clock,
+            &streams,
+        )
+        .await
+}
+
+/// Stop a recording and finalise its file; returns the file path.
+#[tauri::command]
+pub async fn recording_stop(
+    id: SubId,
+    streams: State<'_, BufferStreamState>,
+    state: State<'_, RecordingState>,
+) -> Result<PathBuf, String> {
+    state.stop(id, &streams).await
+}
+
+// --- Buffer transfer ---
+
+/// Load an audio file (WAV, AIFF, FLAC) into `bufnum` on a possibly remote
+/// scsynth, resampled to the server's rate. `progress` gets a message after
+/// each acknowledged batch of `/b_setn`s.
+#[tauri::command]
+pub async fn buffer_upload(
+    bufnum: i32,
+    path: PathBuf,
+    scsynth_addr: String,
+    progress: Channel<UploadProgress>,
+    server_info: State<'_, Arc<ServerInfoService>>,
+) -> Result<UploadInfo, String> {
+    let bytes =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    upload::upload(
+        &scsynth_addr,
+        bufnum,
+        bytes,
+        extension,
+        server_info.sample_rate(&scsynth_addr),
+        |p| {
+            let _ = progress.send(p);
+        },
+    )
+    .await
+}
+
+/// Snapshot of `bufnum`'s whole contents as a 32-bit float WAV file;
+/// arrives in JS as an `ArrayBuffer`.
+#[tauri::command]
+pub async fn buffer_download(bufnum: i32, scsynth_addr: String) -> Result<Response, String> {
+    let (_, wav) = download::download(&scsynth_addr, bufnum).await?;
+    Ok(Response::new(wav))
+}
+
+// --- Pattern sequencer ---
+
+#[tauri::command]
+pub async fn clock_upsert(
+    id: String,
+    bpm: f64,
+    playing: bool,
+    state: State<'_, PatternState>,
+) -> Result<(), String> {
+    state.clock_upsert(ClockSpec { id, bpm, playing }).await
+}
+
+#[tauri::command]
+pub async fn pattern_upsert(
+    spec: PatternSpec,
+    scsynth_addr: String,
+    state: State<'_, PatternState>,
+) -> Result<(), String> {
+    state.pattern_upsert(spec, &scsynth_addr).await
+}
+
+#[tauri::command]
+pub async fn pattern_remove(id: String, state: State<'_, PatternState>) -> Result<(), String> {
+    state.pattern_remove(&id).await
+}
+
+#[tauri::command]
+pub async fn pattern_set_run(
+    id: String,
+    run: bool,
+    state: State<'_, PatternState>,
+) -> Result<(), String> {
+    state.pattern_set_run(&id, run).await
+}
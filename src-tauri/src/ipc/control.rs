@@ -0,0 +1,302 @@
+//! Control-bus and node-control subscriptions, for plugins that display
+//! envelope followers, LFOs or synth parameters rather than audio.
+//!
+//! A poller asks scsynth at `rate` Hz — `/c_getn` for a range of control
+//! buses, `/s_get` for named or indexed controls of a node — and passes a
+//! reply on only when some value changed. Updates go through the buffer
+//! streams' sinks and framing: one frame per update, one channel per bus or
+//! control, and `position` the poll count, so a client can tell how many
+//! polls came back unchanged. Subscribers with the same spec share one
+//! poller; a late one gets the current values straight away.
+
+use super::buffer::{BufferSink, SubId, TickMeta};
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_RATE: u32 = 30;
+pub const MAX_RATE: u32 = 200;
+/// Keeps a `/c_setn` reply well inside one datagram.
+pub const MAX_VALUES: usize = 1024;
+
+fn default_rate() -> u32 {
+    DEFAULT_RATE
+}
+
+fn default_count() -> i32 {
+    1
+}
+
+/// A node control, by index or by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Control {
+    Index(i32),
+    Name(String),
+}
+
+impl Control {
+    fn osc(&self) -> OscType {
+        match self {
+            Control::Index(i) => OscType::Int(*i),
+            Control::Name(n) => OscType::String(n.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ControlSource {
+    /// `count` control buses from `index`.
+    Bus {
+        index: i32,
+        #[serde(default = "default_count")]
+        count: i32,
+    },
+    /// Controls of node `node`.
+    Node { node: i32, controls: Vec<Control> },
+}
+
+impl ControlSource {
+    fn values(&self) -> usize {
+        match self {
+            ControlSource::Bus { count, .. } => (*count).max(0) as usize,
+            ControlSource::Node { controls, .. } => controls.len(),
+        }
+    }
+
+    fn request(&self) -> OscMessage {
+        match self {
+            ControlSource::Bus { index, count } => OscMessage {
+                addr: "/c_getn".into(),
+                args: vec![OscType::Int(*index), OscType::Int(*count)],
+            },
+            ControlSource::Node { node, controls } => OscMessage {
+                addr: "/s_get".into(),
+                args: std::iter::once(OscType::Int(*node))
+                    .chain(controls.iter().map(Control::osc))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Values from a reply to `request()`: `/c_setn index count v…` or
+    /// `/n_set node ctrl v ctrl v…`.
+    fn parse(&self, m: &OscMessage) -> Option<Vec<f32>> {
+        let float = |a: &OscType| match a {
+            OscType::Float(f) => Some(*f),
+            OscType::Int(i) => Some(*i as f32),
+            _ => None,
+        };
+        let values: Vec<f32> = match (self, m.addr.as_str(), m.args.as_slice()) {
+            (
+                ControlSource::Bus { index, count },
+                "/c_setn",
+                [OscType::Int(i), OscType::Int(n), rest @ ..],
+            ) if i == index && n == count => rest.iter().map(float).collect::<Option<_>>()?,
+            (ControlSource::Node { node, .. }, "/n_set", [OscType::Int(n), rest @ ..])
+                if n == node =>
+            {
+                rest.iter()
+                    .skip(1)
+                    .step_by(2)
+                    .map(float)
+                    .collect::<Option<_>>()?
+            }
+            _ => return None,
+        };
+        (values.len() == self.values()).then_some(values)
+    }
+}
+
+/// What to poll, and how often.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlSpec {
+    #[serde(flatten)]
+    pub source: ControlSource,
+    /// Polls per second.
+    #[serde(default = "default_rate")]
+    pub rate: u32,
+}
+
+impl ControlSpec {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_RATE).contains(&self.rate) {
+            return Err(format!("rate must be 1..={MAX_RATE}, got {}", self.rate));
+        }
+        match &self.source {
+            ControlSource::Bus { index, count } => {
+                if *index < 0 || !(1..=MAX_VALUES as i32).contains(count) {
+                    return Err(format!(
+                        "bus range must be index >= 0 and count 1..={MAX_VALUES}, got {index}+{count}"
+                    ));
+                }
+            }
+            ControlSource::Node { controls, .. } => {
+                if controls.is_empty() || controls.len() > MAX_VALUES {
+                    return Err(format!("controls must be 1..={MAX_VALUES}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn label(&self) -> String {
+        match &self.source {
+            ControlSource::Bus { index, count } => format!("bus {index}+{count}"),
+            ControlSource::Node { node, .. } => format!("node {node}"),
+        }
+    }
+}
+
+/// A poller's subscribers and the values they were last sent.
+struct Fanout {
+    sinks: HashMap<SubId, Box<dyn BufferSink>>,
+    last: Option<(TickMeta, Vec<Vec<f32>>)>,
+}
+
+struct PollerHandle {
+    task: JoinHandle<()>,
+    fanout: Arc<Mutex<Fanout>>,
+}
+
+/// A poller per scsynth and spec: bus and node numbers are per server.
+type PollerKey = (String, ControlSpec);
+
+pub struct ControlStreamState {
+    pollers: Mutex<HashMap<PollerKey, PollerHandle>>,
+    index: Mutex<HashMap<SubId, PollerKey>>,
+    next_id: AtomicU64,
+}
+
+impl ControlStreamState {
+    pub fn new() -> Self {
+        Self {
+            pollers: Mutex::new(HashMap::new()),
+            index: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub async fn subscribe(
+        &self,
+        spec: ControlSpec,
+        scsynth_addr: &str,
+        mut sink: Box<dyn BufferSink>,
+    ) -> Result<SubId, String> {
+        spec.validate()?;
+        let sub_id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let key = (scsynth_addr.to_string(), spec);
+        let mut pollers = self.pollers.lock().await;
+        if let Some(h) = pollers.get(&key) {
+            let mut fanout = h.fanout.lock().await;
+            if let Some((meta, values)) = &fanout.last {
+                sink.send(*meta, values);
+            }
+            fanout.sinks.insert(sub_id, sink);
+        } else {
+            let fanout = Arc::new(Mutex::new(Fanout {
+                sinks: HashMap::from([(sub_id, sink)]),
+                last: None,
+            }));
+            let task = spawn_poller(key.1.clone(), key.0.clone(), fanout.clone());
+            pollers.insert(key.clone(), PollerHandle { task, fanout });
+        }
+        drop(pollers);
+        self.index.lock().await.insert(sub_id, key);
+        Ok(sub_id)
+    }
+
+    pub async fn unsubscribe(&self, sub_id: SubId) {
+        let Some(key) = self.index.lock().await.remove(&sub_id) else {
+            return;
+        };
+        let mut pollers = self.pollers.lock().await;
+        let Some(h) = pollers.get(&key) else { return };
+        let empty = {
+            let mut fanout = h.fanout.lock().await;
+            if let Some(mut sink) = fanout.sinks.remove(&sub_id) {
+                sink.close();
+            }
+            fanout.sinks.is_empty()
+        };
+        if empty {
+            if let Some(h) = pollers.remove(&key) {
+                h.task.abort();
+            }
+        }
+    }
+}
+
+fn spawn_poller(spec: ControlSpec, addr: String, fanout: Arc<Mutex<Fanout>>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let label = spec.label();
+        let sock = match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("control[{label}] bind failed: {e}");
+                return;
+            }
+        };
+        if let Err(e) = sock.connect(&addr).await {
+            eprintln!("control[{label}] connect {addr} failed: {e}");
+            return;
+        }
+        let Ok(request) = encoder::encode(&OscPacket::Message(spec.source.request())) else {
+            return;
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_millis(1000 / spec.rate as u64));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut buf = [0u8; 65536];
+        let mut polls: i64 = 0;
+        // Log a `/fail` (node gone, say) once per streak, not every poll.
+        let mut failing = false;
+        eprintln!("control[{label}] polling at {} Hz", spec.rate);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let _ = sock.send(&request).await;
+                    polls += 1;
+                }
+                r = sock.recv(&mut buf) => {
+                    let Ok(n) = r else { break };
+                    let Ok((_, OscPacket::Message(m))) = decoder::decode_udp(&buf[..n]) else {
+                        continue;
+                    };
+                    if m.addr == "/fail" {
+                        if !failing {
+                            eprintln!("control[{label}] {:?}", m.args);
+                            failing = true;
+                        }
+                        continue;
+                    }
+                    let Some(values) = spec.source.parse(&m) else { continue };
+                    failing = false;
+                    let planes: Vec<Vec<f32>> = values.into_iter().map(|v| vec![v]).collect();
+                    let mut guard = fanout.lock().await;
+                    if guard.last.as_ref().is_some_and(|(_, last)| *last == planes) {
+                        continue;
+                    }
+                    let meta = TickMeta {
+                        position: polls,
+                        ..TickMeta::default()
+                    };
+                    guard.sinks.retain(|_, sink| sink.send(meta, &planes));
+                    guard.last = Some((meta, planes));
+                    if guard.sinks.is_empty() {
+                        break;
+                    }
+                }
+            }
+        }
+        eprintln!("control[{label}] stopped");
+    })
+}
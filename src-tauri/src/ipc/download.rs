@@ -0,0 +1,141 @@
+//! One-shot snapshot of a whole buffer as a WAV file, e.g. after a
+//! `RecordBuf` take.
+//!
+//! `/b_query` gives the buffer's size and rate; the samples are then read
+//! with `/b_getn` in datagram-sized chunks, at most `IN_FLIGHT` at a time
+//! so the replies don't overrun our receive buffer. Replies are placed by
+//! start index, and a chunk unanswered for `REPLY_TIMEOUT` is asked again
+//! up to `MAX_TRIES` times before the download fails — a snapshot with
+//! holes in it is worse than none.
+
+use super::buffer::{b_getn, walk_b_setn};
+use super::scsynth;
+use crate::audio;
+use rosc::{decoder, OscPacket, OscType};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Samples per `/b_getn`; the `/b_setn` reply stays under 8 KB.
+const CHUNK_SAMPLES: i32 = 1600;
+const IN_FLIGHT: usize = 16;
+const REPLY_TIMEOUT: Duration = Duration::from_millis(250);
+const MAX_TRIES: u32 = 4;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferInfo {
+    pub bufnum: i32,
+    pub frames: i32,
+    pub channels: i32,
+    pub sample_rate: f64,
+}
+
+/// `/b_query` → `/b_info bufnum frames channels sampleRate`.
+pub async fn query(sock: &UdpSocket, bufnum: i32) -> Result<BufferInfo, String> {
+    scsynth::send(sock, "/b_query", vec![OscType::Int(bufnum)]).await?;
+    let reply = async {
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = sock
+                .recv(&mut buf)
+                .await
+                .map_err(|e| format!("/b_query failed: {e}"))?;
+            let Ok((_, OscPacket::Message(m))) = decoder::decode_udp(&buf[..n]) else {
+                continue;
+            };
+            if m.addr != "/b_info" {
+                continue;
+            }
+            match m.args.as_slice() {
+                [OscType::Int(b), OscType::Int(frames), OscType::Int(channels), OscType::Float(sr), ..]
+                    if *b == bufnum =>
+                {
+                    return Ok(BufferInfo {
+                        bufnum,
+                        frames: *frames,
+                        channels: *channels,
+                        sample_rate: *sr as f64,
+                    });
+                }
+                _ => continue,
+            }
+        }
+    };
+    tokio::time::timeout(scsynth::REPLY_TIMEOUT, reply)
+        .await
+        .map_err(|_| format!("no /b_info for buffer {bufnum}"))?
+}
+
+/// Read all of `bufnum` and encode it as a 32-bit float WAV.
+pub async fn download(scsynth_addr: &str, bufnum: i32) -> Result<(BufferInfo, Vec<u8>), String> {
+    let sock = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("download bind failed: {e}"))?;
+    sock.connect(scsynth_addr)
+        .await
+        .map_err(|e| format!("download connect {scsynth_addr} failed: {e}"))?;
+
+    let info = query(&sock, bufnum).await?;
+    if info.frames <= 0 || info.channels <= 0 {
+        return Err(format!("Buffer {bufnum} is not allocated"));
+    }
+    let total = info.frames * info.channels;
+    let mut data = vec![0.0_f32; total as usize];
+
+    // start sample → (count, sent at, tries)
+    let mut pending: HashMap<i32, (i32, Instant, u32)> = HashMap::new();
+    let mut next = 0;
+    let mut buf = [0u8; 65536];
+    while next < total || !pending.is_empty() {
+        while pending.len() < IN_FLIGHT && next < total {
+            let count = CHUNK_SAMPLES.min(total - next);
+            b_getn(&sock, bufnum, next, count).await;
+            pending.insert(next, (count, Instant::now(), 1));
+            next += count;
+        }
+
+        if let Ok(r) = tokio::time::timeout(REPLY_TIMEOUT, sock.recv(&mut buf)).await {
+            let n = r.map_err(|e| format!("/b_getn failed: {e}"))?;
+            let Ok((_, packet)) = decoder::decode_udp(&buf[..n]) else {
+                continue;
+            };
+            let mut replies = Vec::new();
+            walk_b_setn(&packet, bufnum, &mut replies);
+            for (start, samples) in replies {
+                // Duplicates and stray replies find nothing pending.
+                let Some(&(count, ..)) = pending.get(&start) else {
+                    continue;
+                };
+                if samples.len() == count as usize {
+                    let at = start as usize;
+                    data[at..at + samples.len()].copy_from_slice(&samples);
+                    pending.remove(&start);
+                }
+            }
+        }
+
+        for (&start, (count, sent_at, tries)) in pending.iter_mut() {
+            if sent_at.elapsed() < REPLY_TIMEOUT {
+                continue;
+            }
+            if *tries == MAX_TRIES {
+                return Err(format!(
+                    "buffer {bufnum}: no reply for samples {start}..{} after {MAX_TRIES} tries",
+                    start + *count
+                ));
+            }
+            b_getn(&sock, bufnum, start, *count).await;
+            *sent_at = Instant::now();
+            *tries += 1;
+        }
+    }
+
+    let wav = audio::wav_bytes(&data, info.channels as u16, info.sample_rate.round() as u32)?;
+    eprintln!(
+        "download[buf {bufnum}] {} frames x {} ch @ {} Hz",
+        info.frames, info.channels, info.sample_rate
+    );
+    Ok((info, wav))
+}
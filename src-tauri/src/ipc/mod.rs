@@ -1,3 +1,11 @@
 pub mod buffer;
 pub mod commands;
+pub mod control;
+pub mod download;
+pub mod recording;
+pub mod scope;
+pub mod scsynth;
+pub mod spectrum;
+pub mod trigger;
 pub mod udp;
+pub mod upload;
@@ -0,0 +1,143 @@
+//! Buffer recordings: a `FileSink` subscribed to a buffer stream like any
+//! scope, writing to `<data dir>/recordings/`. Shared by the Tauri commands
+//! and the serve-mode `/recordings` routes.
+
+use super::buffer::{BufferStreamState, FileSink, SubId};
+use crate::audio::Format;
+use crate::clock::{ClockService, DEFAULT_CLOCK};
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+fn default_channels() -> i32 {
+    1
+}
+
+/// What to record; the reader fields mirror `buffer_subscribe`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSpec {
+    pub bufnum: i32,
+    pub frames: i32,
+    #[serde(default = "default_channels")]
+    pub channels: i32,
+    pub chunk: i32,
+    /// Fallback for when scsynth hasn't answered `/status` yet.
+    pub sample_rate: i32,
+    #[serde(default)]
+    pub phase_tracked: bool,
+    /// Phase clock to follow when `phase_tracked`; default the global one.
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(default)]
+    pub format: Option<Format>,
+    /// File name stem; default `buf<bufnum>`. A timestamp is appended.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub id: SubId,
+    pub path: PathBuf,
+}
+
+pub struct RecordingState {
+    active: Mutex<HashMap<SubId, PathBuf>>,
+}
+
+impl RecordingState {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open the file and subscribe it to the buffer stream.
+    pub async fn start(
+        &self,
+        spec: RecordingSpec,
+        data_dir: &Path,
+        scsynth_addr: &str,
+        sample_rate: i32,
+        clock: &ClockService,
+        streams: &BufferStreamState,
+    ) -> Result<RecordingInfo, String> {
+        let dir = config::recordings_dir(data_dir);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Error creating {}: {e}", dir.display()))?;
+        let format = spec.format.unwrap_or(Format::Wav);
+        let stem = match spec.name.as_deref() {
+            Some(name) => sanitize(name)?,
+            None => format!("buf{}", spec.bufnum),
+        };
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let path = dir.join(format!("{stem}-{stamp}.{}", format.extension()));
+
+        let sink = Box::new(FileSink::create(
+            &path,
+            format,
+            spec.channels,
+            sample_rate,
+        )?);
+        let clock = if spec.phase_tracked {
+            Some(
+                clock
+                    .clock(spec.clock.as_deref().unwrap_or(DEFAULT_CLOCK))
+                    .await,
+            )
+        } else {
+            None
+        };
+        let sub = streams
+            .subscribe(
+                spec.bufnum,
+                spec.frames,
+                spec.channels,
+                spec.chunk,
+                sample_rate,
+                scsynth_addr,
+                clock,
+                sink,
+            )
+            .await
+            .inspect_err(|_| {
+                let _ = std::fs::remove_file(&path);
+            })?;
+        eprintln!("recording[{}] started from buf {}", path.display(), spec.bufnum);
+        self.active.lock().await.insert(sub.id, path.clone());
+        Ok(RecordingInfo { id: sub.id, path })
+    }
+
+    /// Unsubscribe, which finalises the file. Returns its path.
+    pub async fn stop(&self, id: SubId, streams: &BufferStreamState) -> Result<PathBuf, String> {
+        let path = self
+            .active
+            .lock()
+            .await
+            .remove(&id)
+            .ok_or_else(|| format!("Unknown recording {id}"))?;
+        streams.unsubscribe(id).await;
+        Ok(path)
+    }
+}
+
+/// Keep recording names to a single path component.
+fn sanitize(name: &str) -> Result<String, String> {
+    let ok = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if ok {
+        Ok(name.to_string())
+    } else {
+        Err(format!("Invalid recording name \"{name}\""))
+    }
+}
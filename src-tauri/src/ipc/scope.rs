@@ -0,0 +1,476 @@
+//! Scope buffers read straight from scsynth's shared memory (`server_shm`),
+//! for scopes that can't live with `/b_getn` round-trips.
+//!
+//! scsynth keeps a boost.interprocess segment `SuperColliderServer_<port>`
+//! (`/dev/shm` on Linux) holding its control buses and the scope buffers
+//! `ScopeOut2` writes into. We map it read-write and follow the writer's
+//! triple-buffer protocol, lock-free: the writer fills its `in` slot, swaps
+//! it with `stage` and raises `changed`; the reader clears `changed` and
+//! swaps `stage` with its `out` slot, which then stays its own until the
+//! next swap. Each new window goes to the sinks as one batch.
+//!
+//! The layout mirrors SuperCollider's `common/server_shm.hpp` and
+//! `scope_buffer.hpp` on 64-bit. Rather than walk boost's name index, whose
+//! layout changes between boost versions, the `server_shared_memory` object
+//! is found by its name: boost stores a named object's name right after its
+//! value. Only works for a scsynth on this machine.
+
+use super::buffer::{BufferSink, SubId, TickMeta};
+use memmap2::MmapMut;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// One look per display frame.
+const POLL_MS: u64 = 16;
+
+/// `server_shared_memory`: `int num_control_busses, num_scope_buffers;
+/// offset_ptr<float> control_busses_; offset_ptr<scope_buffer>
+/// scope_buffers;`
+pub const SERVER_SIZE: usize = 24;
+pub const SERVER_NUM_SCOPES: usize = 4;
+pub const SERVER_SCOPES_PTR: usize = 16;
+
+/// `scope_buffer`: `atomic<int> _status; unsigned _size, _channels;
+/// offset_ptr<float> _data; data_desc _state[3]; atomic<int> _stage, _in,
+/// _out; atomic<bool> _changed;`, where `data_desc` is
+/// `{ offset_ptr<float> data; unsigned frames; }`.
+pub const SCOPE_SIZE: usize = 88;
+pub const SCOPE_STATUS: usize = 0;
+pub const SCOPE_CHANNELS: usize = 8;
+pub const SCOPE_STATE: usize = 24;
+pub const DESC_SIZE: usize = 16;
+pub const DESC_FRAMES: usize = 8;
+pub const SCOPE_STAGE: usize = 72;
+pub const SCOPE_IN: usize = 76;
+pub const SCOPE_OUT: usize = 80;
+pub const SCOPE_CHANGED: usize = 84;
+/// `_status` once a `ScopeOut2` has claimed the buffer.
+pub const STATUS_INITIALIZED: i32 = 1;
+
+/// boost's `offset_ptr` encodes null as offset 1.
+const OFFSET_NULL: i64 = 1;
+
+/// Name of scsynth's segment, and of the server object inside it.
+pub fn segment_name(port: u16) -> String {
+    format!("SuperColliderServer_{port}")
+}
+
+/// Port of a `host:port` scsynth address.
+pub fn port_of(scsynth_addr: &str) -> Result<u16, String> {
+    scsynth_addr
+        .rsplit_once(':')
+        .and_then(|(_, p)| p.parse().ok())
+        .ok_or_else(|| format!("No port in scsynth address \"{scsynth_addr}\""))
+}
+
+/// A mapped `server_shm` segment.
+pub struct ServerShm {
+    /// Kept for the mapping's lifetime; accessed through `ptr`.
+    _map: MmapMut,
+    ptr: *mut u8,
+    len: usize,
+    num_scopes: usize,
+    /// Offset of `scope_buffers[0]`.
+    scopes: usize,
+}
+
+impl ServerShm {
+    /// Map the segment of the scsynth listening on `port`.
+    pub fn open(port: u16) -> Result<Self, String> {
+        let name = segment_name(port);
+        Self::open_path(&Path::new("/dev/shm").join(&name), &name)
+    }
+
+    /// Map `path` and find the server object named `name` in it.
+    pub fn open_path(path: &Path, name: &str) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+        // SAFETY: the segment is shared with scsynth by design. Every access
+        // below is bounds-checked against the map, and the fields both
+        // sides write go through atomics.
+        let mut map = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|e| format!("Cannot map {}: {e}", path.display()))?;
+        let (num_scopes, scopes) = locate(&map, name)
+            .ok_or_else(|| format!("No scsynth server object in {}", path.display()))?;
+        Ok(Self {
+            ptr: map.as_mut_ptr(),
+            len: map.len(),
+            _map: map,
+            num_scopes,
+            scopes,
+        })
+    }
+
+    pub fn num_scopes(&self) -> usize {
+        self.num_scopes
+    }
+
+    /// Channels of scope buffer `index`, once a `ScopeOut2` has claimed it.
+    pub fn channels(&self, index: usize) -> Option<usize> {
+        let base = self.scopes + index * SCOPE_SIZE;
+        if self.i32_at(base + SCOPE_STATUS).load(Ordering::Acquire) != STATUS_INITIALIZED {
+            return None;
+        }
+        usize::try_from(self.i32_at(base + SCOPE_CHANNELS).load(Ordering::Relaxed)).ok()
+    }
+
+    fn i32_at(&self, off: usize) -> &AtomicI32 {
+        assert!(off.is_multiple_of(4) && off + 4 <= self.len);
+        // SAFETY: in bounds and aligned (the map is page-aligned).
+        unsafe { &*(self.ptr.add(off) as *const AtomicI32) }
+    }
+
+    fn offset_at(&self, off: usize) -> Option<usize> {
+        assert!(off + 8 <= self.len);
+        // SAFETY: in bounds; the writer doesn't move a published slot.
+        let raw = unsafe { (self.ptr.add(off) as *const i64).read_unaligned() };
+        target(off, raw, self.len)
+    }
+
+    /// The newest window of scope buffer `index`, de-interleaved, if the
+    /// writer published one since the last call.
+    fn pull(&self, index: usize) -> Option<Vec<Vec<f32>>> {
+        let base = self.scopes + index * SCOPE_SIZE;
+        if self.i32_at(base + SCOPE_STATUS).load(Ordering::Acquire) != STATUS_INITIALIZED {
+            return None;
+        }
+        // SAFETY: `base + SCOPE_CHANGED` is inside the map (checked when the
+        // scope table was located), and a bool has no alignment needs.
+        let changed = unsafe { &*(self.ptr.add(base + SCOPE_CHANGED) as *const AtomicBool) };
+        if !changed.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        let out = self.i32_at(base + SCOPE_OUT);
+        let slot = self
+            .i32_at(base + SCOPE_STAGE)
+            .swap(out.load(Ordering::Acquire), Ordering::AcqRel);
+        out.store(slot, Ordering::Release);
+
+        let slot = usize::try_from(slot).ok().filter(|s| *s < 3)?;
+        let channels = self.i32_at(base + SCOPE_CHANNELS).load(Ordering::Relaxed) as usize;
+        let desc = base + SCOPE_STATE + slot * DESC_SIZE;
+        let frames = self.i32_at(desc + DESC_FRAMES).load(Ordering::Relaxed) as usize;
+        let data = self.offset_at(desc)?;
+        let len = frames.checked_mul(channels)?.checked_mul(4)?;
+        if channels == 0 || frames == 0 || data.checked_add(len)? > self.len {
+            return None;
+        }
+        // SAFETY: in bounds (checked above), and the `out` slot is ours
+        // until the next swap.
+        let bytes = unsafe { std::slice::from_raw_parts(self.ptr.add(data), len) };
+        Some(
+            (0..channels)
+                .map(|c| {
+                    (0..frames)
+                        .map(|f| {
+                            let at = (f * channels + c) * 4;
+                            f32::from_ne_bytes(bytes[at..at + 4].try_into().unwrap())
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+// SAFETY: `ptr` points into `_map`, which lives and moves with us; the
+// segment itself is process-shared memory, not tied to a thread.
+unsafe impl Send for ServerShm {}
+
+/// Find the server object named `name`: its value sits right before the
+/// name. Returns the scope count and the offset of the scope table.
+fn locate(map: &[u8], name: &str) -> Option<(usize, usize)> {
+    let mut needle = name.as_bytes().to_vec();
+    needle.push(0);
+    let mut from = 0;
+    while let Some(at) = map[from..]
+        .windows(needle.len())
+        .position(|w| w == needle.as_slice())
+    {
+        let pos = from + at;
+        from = pos + 1;
+        let Some(server) = pos.checked_sub(SERVER_SIZE) else {
+            continue;
+        };
+        if !server.is_multiple_of(8) {
+            continue;
+        }
+        let num_scopes = i32::from_ne_bytes(
+            map[server + SERVER_NUM_SCOPES..server + SERVER_NUM_SCOPES + 4]
+                .try_into()
+                .unwrap(),
+        );
+        let Ok(num_scopes) = usize::try_from(num_scopes) else {
+            continue;
+        };
+        let Some(scopes) = resolve(map, server + SERVER_SCOPES_PTR) else {
+            continue;
+        };
+        let fits = num_scopes
+            .checked_mul(SCOPE_SIZE)
+            .and_then(|n| n.checked_add(scopes))
+            .is_some_and(|end| end <= map.len());
+        if num_scopes > 0 && scopes.is_multiple_of(8) && fits {
+            return Some((num_scopes, scopes));
+        }
+    }
+    None
+}
+
+/// Target of the `offset_ptr` at `off`, as an offset into `map`.
+fn resolve(map: &[u8], off: usize) -> Option<usize> {
+    let raw = i64::from_ne_bytes(map.get(off..off + 8)?.try_into().unwrap());
+    target(off, raw, map.len())
+}
+
+/// Where an `offset_ptr` stored at `off` with value `raw` points.
+fn target(off: usize, raw: i64, len: usize) -> Option<usize> {
+    if raw == OFFSET_NULL {
+        return None;
+    }
+    let target = usize::try_from(off as i64 + raw).ok()?;
+    (target < len).then_some(target)
+}
+
+/// Poll scope buffer `index` every `POLL_MS` and hand each new window to
+/// the sinks; stops once they're all gone. `position` counts the frames
+/// delivered, since scope windows carry no clock position of their own.
+pub(super) fn spawn_scope_reader(
+    shm: ServerShm,
+    index: usize,
+    sinks: Arc<Mutex<HashMap<SubId, Box<dyn BufferSink>>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(POLL_MS));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut position: i64 = 0;
+        let mut windows: u64 = 0;
+        eprintln!("scope[{index}] reading from shared memory");
+        loop {
+            interval.tick().await;
+            let Some(window) = shm.pull(index) else {
+                continue;
+            };
+            let meta = TickMeta {
+                position,
+                ..TickMeta::default()
+            };
+            position += window[0].len() as i64;
+            windows += 1;
+            let mut guard = sinks.lock().await;
+            guard.retain(|_, sink| sink.send(meta, &window));
+            if guard.is_empty() {
+                break;
+            }
+        }
+        eprintln!("scope[{index}] stopped after {windows} windows");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+
+    const SEG: usize = 64 * 1024;
+    const SERVER: usize = 4096;
+    const SCOPES: usize = 8192;
+    const NUM_SCOPES: usize = 4;
+    const DATA: usize = 16384;
+    const NAME: &str = "SuperColliderServer_57110";
+
+    /// A segment laid out like scsynth's, one scope claimed, mapped a second
+    /// time here to play the writer.
+    struct Fake {
+        path: PathBuf,
+        map: MmapMut,
+        base: usize,
+    }
+
+    impl Fake {
+        fn new(channels: usize, frames: usize) -> Self {
+            static SEQ: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "sc-app-scope-{}-{}",
+                std::process::id(),
+                SEQ.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            file.set_len(SEG as u64).unwrap();
+            let map = unsafe { MmapMut::map_mut(&file) }.unwrap();
+            let mut fake = Self {
+                path,
+                map,
+                base: SCOPES + SCOPE_SIZE,
+            };
+            // A stray copy of the name with nothing sensible before it.
+            fake.map[100..100 + NAME.len()].copy_from_slice(NAME.as_bytes());
+            fake.put_i32(SERVER, 1024);
+            fake.put_i32(SERVER + SERVER_NUM_SCOPES, NUM_SCOPES as i32);
+            fake.put_ptr(SERVER + SERVER_SCOPES_PTR, SCOPES);
+            let name = SERVER + SERVER_SIZE;
+            fake.map[name..name + NAME.len()].copy_from_slice(NAME.as_bytes());
+
+            let base = fake.base;
+            fake.put_i32(base + SCOPE_STATUS, STATUS_INITIALIZED);
+            fake.put_i32(base + SCOPE_CHANNELS, channels as i32);
+            for slot in 0..3 {
+                let desc = base + SCOPE_STATE + slot * DESC_SIZE;
+                fake.put_ptr(desc, DATA + slot * 4096);
+                fake.put_i32(desc + DESC_FRAMES, frames as i32);
+            }
+            fake.put_i32(base + SCOPE_IN, 0);
+            fake.put_i32(base + SCOPE_STAGE, 1);
+            fake.put_i32(base + SCOPE_OUT, 2);
+            fake
+        }
+
+        fn put_i32(&mut self, off: usize, v: i32) {
+            self.map[off..off + 4].copy_from_slice(&v.to_ne_bytes());
+        }
+
+        fn get_i32(&self, off: usize) -> i32 {
+            i32::from_ne_bytes(self.map[off..off + 4].try_into().unwrap())
+        }
+
+        fn put_ptr(&mut self, off: usize, target: usize) {
+            self.put_raw_ptr(off, target as i64 - off as i64);
+        }
+
+        fn put_raw_ptr(&mut self, off: usize, raw: i64) {
+            self.map[off..off + 8].copy_from_slice(&raw.to_ne_bytes());
+        }
+
+        /// Fill slot `slot` with interleaved `samples` and publish it as
+        /// `stage`, as the writer's swap would leave it.
+        fn publish(&mut self, slot: usize, samples: &[f32]) {
+            let data = DATA + slot * 4096;
+            for (i, s) in samples.iter().enumerate() {
+                self.map[data + i * 4..data + i * 4 + 4].copy_from_slice(&s.to_ne_bytes());
+            }
+            self.put_i32(self.base + SCOPE_STAGE, slot as i32);
+            self.map[self.base + SCOPE_CHANGED] = 1;
+        }
+
+        fn open(&self) -> ServerShm {
+            ServerShm::open_path(&self.path, NAME).unwrap()
+        }
+    }
+
+    impl Drop for Fake {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn finds_the_server_object() {
+        let fake = Fake::new(1, 4);
+        let shm = fake.open();
+        assert_eq!(shm.num_scopes(), NUM_SCOPES);
+        assert_eq!(shm.scopes, SCOPES);
+        assert!(ServerShm::open_path(&fake.path, "SuperColliderServer_1").is_err());
+    }
+
+    #[test]
+    fn pull_deinterleaves_stage_and_swaps_it_with_out() {
+        let mut fake = Fake::new(2, 3);
+        fake.publish(1, &[1.0, -1.0, 2.0, -2.0, 3.0, -3.0]);
+        let shm = fake.open();
+        assert_eq!(
+            shm.pull(1),
+            Some(vec![vec![1.0, 2.0, 3.0], vec![-1.0, -2.0, -3.0]])
+        );
+        // `out` now owns slot 1; the old `out` slot went back to `stage`.
+        assert_eq!(fake.get_i32(fake.base + SCOPE_OUT), 1);
+        assert_eq!(fake.get_i32(fake.base + SCOPE_STAGE), 2);
+        assert_eq!(fake.map[fake.base + SCOPE_CHANGED], 0);
+        assert_eq!(shm.pull(1), None);
+
+        // The next window comes from the slot the writer published.
+        fake.publish(0, &[5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+        assert_eq!(
+            shm.pull(1),
+            Some(vec![vec![5.0, 7.0, 9.0], vec![6.0, 8.0, 10.0]])
+        );
+        assert_eq!(fake.get_i32(fake.base + SCOPE_OUT), 0);
+        assert_eq!(fake.get_i32(fake.base + SCOPE_STAGE), 1);
+    }
+
+    #[test]
+    fn unclaimed_scope_gives_nothing() {
+        let mut fake = Fake::new(2, 1);
+        assert_eq!(fake.open().channels(1), Some(2));
+        fake.publish(1, &[1.0, 2.0]);
+        fake.put_i32(fake.base + SCOPE_STATUS, 0);
+        assert_eq!(fake.open().pull(1), None);
+        assert_eq!(fake.open().channels(1), None);
+    }
+
+    #[test]
+    fn bad_descriptors_give_none() {
+        let desc = |fake: &Fake| fake.base + SCOPE_STATE + DESC_SIZE;
+
+        let mut fake = Fake::new(1, 2);
+        fake.publish(1, &[1.0, 2.0]);
+        let d = desc(&fake);
+        fake.put_raw_ptr(d, OFFSET_NULL);
+        assert_eq!(fake.open().pull(1), None);
+
+        let mut fake = Fake::new(1, 2);
+        fake.publish(1, &[1.0, 2.0]);
+        let d = desc(&fake);
+        fake.put_raw_ptr(d, SEG as i64);
+        assert_eq!(fake.open().pull(1), None);
+
+        let mut fake = Fake::new(1, 2);
+        fake.publish(1, &[1.0, 2.0]);
+        let d = desc(&fake);
+        fake.put_raw_ptr(d, -(d as i64) - 8);
+        assert_eq!(fake.open().pull(1), None);
+
+        // Data in bounds, but frames running off the end of the map.
+        let mut fake = Fake::new(2, 2);
+        fake.publish(1, &[1.0, 2.0, 3.0, 4.0]);
+        let d = desc(&fake);
+        fake.put_i32(d + DESC_FRAMES, SEG as i32);
+        assert_eq!(fake.open().pull(1), None);
+
+        let mut fake = Fake::new(2, 2);
+        fake.publish(1, &[1.0, 2.0, 3.0, 4.0]);
+        let d = desc(&fake);
+        fake.put_i32(d + DESC_FRAMES, -1);
+        assert_eq!(fake.open().pull(1), None);
+
+        // A stage index outside the triple buffer.
+        let mut fake = Fake::new(1, 2);
+        fake.publish(1, &[1.0, 2.0]);
+        fake.put_i32(fake.base + SCOPE_STAGE, 7);
+        assert_eq!(fake.open().pull(1), None);
+    }
+
+    #[test]
+    fn offset_ptr_targets() {
+        assert_eq!(target(16, OFFSET_NULL, 64), None);
+        assert_eq!(target(16, 8, 64), Some(24));
+        assert_eq!(target(16, -16, 64), Some(0));
+        assert_eq!(target(16, -17, 64), None);
+        assert_eq!(target(16, 48, 64), None);
+    }
+}
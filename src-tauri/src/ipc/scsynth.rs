@@ -0,0 +1,81 @@
+//! Asynchronous scsynth commands from a backend-owned socket: send, then
+//! wait for the reply that says the server got there. Used where the
+//! backend drives scsynth itself (record, buffer upload) rather than
+//! relaying the frontend's OSC.
+
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// How long to wait for `/done`, `/fail` or `/synced`.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Send an asynchronous command (`/b_alloc`, `/b_free`, …) and wait for its
+/// `/done` (or `/fail`).
+pub async fn command(sock: &UdpSocket, addr: &str, args: Vec<OscType>) -> Result<(), String> {
+    send(sock, addr, args).await?;
+    wait(sock, addr, |m| {
+        let for_us = matches!(m.args.first(), Some(OscType::String(s)) if s == addr);
+        match m.addr.as_str() {
+            "/done" if for_us => Some(Ok(())),
+            "/fail" if for_us => {
+                let reason = match m.args.get(1) {
+                    Some(OscType::String(s)) => s.clone(),
+                    _ => "unknown error".to_string(),
+                };
+                Some(Err(format!("{addr} failed: {reason}")))
+            }
+            _ => None,
+        }
+    })
+    .await
+}
+
+/// `/sync id` and wait for `/synced id`: every command sent before it on
+/// this socket has been processed.
+pub async fn sync(sock: &UdpSocket, id: i32) -> Result<(), String> {
+    send(sock, "/sync", vec![OscType::Int(id)]).await?;
+    wait(sock, "/sync", |m| {
+        (m.addr == "/synced" && matches!(m.args.first(), Some(OscType::Int(i)) if *i == id))
+            .then_some(Ok(()))
+    })
+    .await
+}
+
+pub async fn send(sock: &UdpSocket, addr: &str, args: Vec<OscType>) -> Result<(), String> {
+    let msg = OscMessage {
+        addr: addr.into(),
+        args,
+    };
+    let bytes = encoder::encode(&OscPacket::Message(msg)).map_err(|e| e.to_string())?;
+    sock.send(&bytes)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("{addr} send failed: {e}"))
+}
+
+/// Read replies until `matches` decides one, or `REPLY_TIMEOUT`.
+async fn wait(
+    sock: &UdpSocket,
+    addr: &str,
+    matches: impl Fn(&OscMessage) -> Option<Result<(), String>>,
+) -> Result<(), String> {
+    let reply = async {
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = sock
+                .recv(&mut buf)
+                .await
+                .map_err(|e| format!("{addr} failed: {e}"))?;
+            let Ok((_, OscPacket::Message(m))) = decoder::decode_udp(&buf[..n]) else {
+                continue;
+            };
+            if let Some(result) = matches(&m) {
+                return result;
+            }
+        }
+    };
+    tokio::time::timeout(REPLY_TIMEOUT, reply)
+        .await
+        .map_err(|_| format!("no reply to {addr}"))?
+}
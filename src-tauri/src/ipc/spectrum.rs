@@ -0,0 +1,274 @@
+//! Magnitude spectra of buffer streams, so plugin analysers don't ship FFT
+//! code to the browser. A `SpectrumSink` sits on a buffer reader like any
+//! other sink: it windows the incoming frames, runs an FFT every `hop`
+//! frames and hands each spectrum on as one batch with one plane per
+//! channel — so the Tauri channel and WebSocket framing are the buffer
+//! stream's own, with bins in place of frames and `position` the first
+//! frame of the analysed window.
+//!
+//! Magnitudes are scaled by the window's coherent gain, so a full-scale
+//! sine reads ~1.0 (0 dB). With `logBins` the linear bins are folded into
+//! that many log-spaced bands from `LOG_MIN_HZ` to Nyquist, each the
+//! loudest bin it covers; narrow low bands repeat their nearest bin.
+
+use super::buffer::{BufferSink, BufferStreamState, Subscription, TickMeta};
+use crate::clock::{ClockService, DEFAULT_CLOCK};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::Deserialize;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+const MIN_SIZE: usize = 32;
+const MAX_SIZE: usize = 32768;
+/// Bottom edge of the first log band.
+const LOG_MIN_HZ: f32 = 20.0;
+/// dB value for silence, instead of -inf.
+const DB_FLOOR: f32 = -120.0;
+
+fn default_channels() -> i32 {
+    1
+}
+
+fn default_size() -> usize {
+    2048
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Window {
+    #[default]
+    Hann,
+    Blackman,
+}
+
+impl Window {
+    fn coefficients(self, size: usize) -> Vec<f32> {
+        let n = size as f32;
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * PI * i as f32 / n;
+                match self {
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// How to analyse: FFT size and hop in frames, window, and output scaling.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumConfig {
+    #[serde(default = "default_size")]
+    pub size: usize,
+    /// Frames between spectra; default half the size.
+    #[serde(default)]
+    pub hop: Option<usize>,
+    #[serde(default)]
+    pub window: Window,
+    /// Fold into this many log-spaced bands instead of `size / 2 + 1`
+    /// linear bins.
+    #[serde(default)]
+    pub log_bins: Option<usize>,
+    /// Decibels (floored at -120) instead of linear magnitude.
+    #[serde(default)]
+    pub db: bool,
+}
+
+/// A spectrum subscription; the reader fields mirror `buffer_subscribe`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumSpec {
+    pub bufnum: i32,
+    pub frames: i32,
+    #[serde(default = "default_channels")]
+    pub channels: i32,
+    pub chunk: i32,
+    /// Fallback for when scsynth hasn't answered `/status` yet.
+    pub sample_rate: i32,
+    #[serde(default)]
+    pub phase_tracked: bool,
+    /// Phase clock to follow when `phase_tracked`; default the global one.
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(flatten)]
+    pub analysis: SpectrumConfig,
+}
+
+impl SpectrumSpec {
+    /// Wrap `sink` in a `SpectrumSink` and subscribe it to the buffer.
+    pub async fn subscribe(
+        &self,
+        sink: Box<dyn BufferSink>,
+        scsynth_addr: &str,
+        sample_rate: i32,
+        clock: &ClockService,
+        streams: &BufferStreamState,
+    ) -> Result<Subscription, String> {
+        let sink = Box::new(SpectrumSink::new(sink, self.analysis, sample_rate)?);
+        let clock = if self.phase_tracked {
+            Some(
+                clock
+                    .clock(self.clock.as_deref().unwrap_or(DEFAULT_CLOCK))
+                    .await,
+            )
+        } else {
+            None
+        };
+        streams
+            .subscribe(
+                self.bufnum,
+                self.frames,
+                self.channels,
+                self.chunk,
+                sample_rate,
+                scsynth_addr,
+                clock,
+                sink,
+            )
+            .await
+    }
+}
+
+pub struct SpectrumSink {
+    inner: Box<dyn BufferSink>,
+    size: usize,
+    hop: usize,
+    db: bool,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// `2 / sum(window)`: undoes the window's coherent gain and folds in
+    /// the negative-frequency half.
+    gain: f32,
+    /// Linear bin range `[start, end)` of each log band.
+    bands: Option<Vec<(usize, usize)>>,
+    /// Per-channel frames not yet consumed by a hop.
+    pending: Vec<Vec<f32>>,
+    /// Position of `pending`'s first frame.
+    start: i64,
+    /// Flags and losses of batches not yet reflected in a spectrum.
+    carry: TickMeta,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl SpectrumSink {
+    pub fn new(
+        inner: Box<dyn BufferSink>,
+        config: SpectrumConfig,
+        sample_rate: i32,
+    ) -> Result<Self, String> {
+        let size = config.size;
+        if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
+            return Err(format!(
+                "spectrum size must be {MIN_SIZE}..={MAX_SIZE}, got {size}"
+            ));
+        }
+        let hop = config.hop.unwrap_or(size / 2);
+        if hop == 0 || hop > size {
+            return Err(format!("spectrum hop must be 1..={size}, got {hop}"));
+        }
+        let bands = match config.log_bins {
+            Some(0) => return Err("spectrum logBins must be > 0".to_string()),
+            Some(n) => Some(log_bands(n, size, sample_rate)),
+            None => None,
+        };
+        let window = config.window.coefficients(size);
+        let gain = 2.0 / window.iter().sum::<f32>();
+        Ok(Self {
+            inner,
+            size,
+            hop,
+            db: config.db,
+            fft: FftPlanner::new().plan_fft_forward(size),
+            window,
+            gain,
+            bands,
+            pending: Vec::new(),
+            start: 0,
+            carry: TickMeta::default(),
+            scratch: Vec::with_capacity(size),
+        })
+    }
+
+    fn analyse(&mut self, ch: usize) -> Vec<f32> {
+        self.scratch.clear();
+        self.scratch.extend(
+            self.pending[ch][..self.size]
+                .iter()
+                .zip(&self.window)
+                .map(|(s, w)| Complex::new(s * w, 0.0)),
+        );
+        self.fft.process(&mut self.scratch);
+        let mags: Vec<f32> = self.scratch[..=self.size / 2]
+            .iter()
+            .map(|c| c.norm() * self.gain)
+            .collect();
+        let out = match &self.bands {
+            Some(bands) => bands
+                .iter()
+                .map(|&(lo, hi)| mags[lo..hi].iter().copied().fold(0.0, f32::max))
+                .collect(),
+            None => mags,
+        };
+        if self.db {
+            out.into_iter()
+                .map(|m| (20.0 * m.log10()).max(DB_FLOOR))
+                .collect()
+        } else {
+            out
+        }
+    }
+}
+
+impl BufferSink for SpectrumSink {
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        if self.pending.len() != tick.len() {
+            self.pending = vec![Vec::with_capacity(2 * self.size); tick.len()];
+        }
+        if self.pending[0].is_empty() {
+            self.start = meta.position;
+        }
+        self.carry.absorb(meta);
+        for (pending, plane) in self.pending.iter_mut().zip(tick) {
+            pending.extend_from_slice(plane);
+        }
+        while self.pending[0].len() >= self.size {
+            let spectra: Vec<Vec<f32>> =
+                (0..self.pending.len()).map(|ch| self.analyse(ch)).collect();
+            let meta = TickMeta {
+                position: self.start,
+                ..std::mem::take(&mut self.carry)
+            };
+            if !self.inner.send(meta, &spectra) {
+                return false;
+            }
+            for pending in &mut self.pending {
+                pending.drain(..self.hop);
+            }
+            self.start += self.hop as i64;
+        }
+        true
+    }
+    fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+/// `n` log-spaced bands from `LOG_MIN_HZ` to Nyquist as linear bin ranges.
+/// Every band covers at least one bin.
+fn log_bands(n: usize, size: usize, sample_rate: i32) -> Vec<(usize, usize)> {
+    let nyquist_bin = size / 2;
+    let bin_hz = sample_rate.max(1) as f32 / size as f32;
+    let lo = (LOG_MIN_HZ / bin_hz).clamp(1.0, nyquist_bin as f32);
+    let hi = nyquist_bin as f32 + 1.0;
+    let edge = |k: usize| lo * (hi / lo).powf(k as f32 / n as f32);
+    (0..n)
+        .map(|k| {
+            let start = (edge(k).floor() as usize).min(nyquist_bin);
+            let end = (edge(k + 1).ceil() as usize).clamp(start + 1, nyquist_bin + 1);
+            (start, end)
+        })
+        .collect()
+}
@@ -0,0 +1,441 @@
+//! Oscilloscope-style triggering for buffer streams, so periodic waveforms
+//! stand still on screen instead of scrolling.
+//!
+//! `TriggerSink` watches one channel for an edge through `level` — a
+//! Schmitt trigger: the signal must first leave the far side of the
+//! hysteresis band to arm it — and passes on fixed windows of `frames`
+//! frames starting `preTrigger` frames before the crossing, so the edge
+//! always lands at the same spot. Triggers are ignored until the window
+//! after the last one is complete and `holdoff` frames have passed since
+//! its edge. Like a hardware scope:
+//!
+//! - `normal`: only triggered windows;
+//! - `auto` (default): if nothing triggers for a window's length (at least
+//!   `AUTO_TIMEOUT_MS`), the latest frames go out free-running, flagged
+//!   `FLAG_AUTO`, so a flat or untriggerable signal still shows;
+//! - `single`: the first triggered window, then nothing; subscribe again
+//!   to re-arm.
+//!
+//! A window's `position` is its first frame; the edge is at `position +
+//! preTrigger`. A jump in the incoming positions (clock resync, reader
+//! restart) drops the history and disarms.
+
+use super::buffer::{BufferSink, TickMeta, FLAG_AUTO};
+use serde::Deserialize;
+
+const MAX_FRAMES: usize = 1 << 20;
+const AUTO_TIMEOUT_MS: usize = 100;
+
+fn default_hysteresis() -> f32 {
+    0.01
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerMode {
+    #[default]
+    Auto,
+    Normal,
+    Single,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Slope {
+    #[default]
+    Rising,
+    Falling,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerConfig {
+    #[serde(default)]
+    pub mode: TriggerMode,
+    /// Channel the trigger watches.
+    #[serde(default)]
+    pub channel: usize,
+    #[serde(default)]
+    pub level: f32,
+    #[serde(default)]
+    pub slope: Slope,
+    /// Width of the band the signal must leave, on the far side of
+    /// `level`, before the next crossing counts.
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f32,
+    /// Window length.
+    pub frames: usize,
+    /// Frames before the edge in each window; default half the window.
+    #[serde(default)]
+    pub pre_trigger: Option<usize>,
+    /// Minimum frames from one edge to the next.
+    #[serde(default)]
+    pub holdoff: usize,
+}
+
+pub struct TriggerSink {
+    inner: Box<dyn BufferSink>,
+    config: TriggerConfig,
+    pre: usize,
+    /// Frames without a window after which auto mode free-runs.
+    auto_frames: i64,
+    /// Recent frames per channel; `history[c][0]` is at `start`.
+    history: Vec<Vec<f32>>,
+    start: i64,
+    /// Next frame to test for an edge.
+    scan: i64,
+    armed: bool,
+    /// Edges before this frame are ignored.
+    holdoff_until: i64,
+    /// First frame of a triggered window still filling up.
+    pending: Option<i64>,
+    /// End of the last window passed on (or of the stream's start).
+    last_window: i64,
+    /// Single mode has fired.
+    done: bool,
+    carry: TickMeta,
+}
+
+impl TriggerSink {
+    pub fn new(
+        inner: Box<dyn BufferSink>,
+        config: TriggerConfig,
+        sample_rate: i32,
+    ) -> Result<Self, String> {
+        if !(1..=MAX_FRAMES).contains(&config.frames) {
+            return Err(format!(
+                "trigger frames must be 1..={MAX_FRAMES}, got {}",
+                config.frames
+            ));
+        }
+        let pre = config.pre_trigger.unwrap_or(config.frames / 2);
+        if pre > config.frames {
+            return Err(format!(
+                "trigger preTrigger must be <= frames ({}), got {pre}",
+                config.frames
+            ));
+        }
+        if config.hysteresis.is_nan() || config.hysteresis < 0.0 {
+            return Err("trigger hysteresis must be >= 0".to_string());
+        }
+        let timeout = sample_rate.max(1) as usize * AUTO_TIMEOUT_MS / 1000;
+        Ok(Self {
+            inner,
+            config,
+            pre,
+            auto_frames: timeout.max(config.frames) as i64,
+            history: Vec::new(),
+            start: 0,
+            scan: 0,
+            armed: false,
+            holdoff_until: i64::MIN,
+            pending: None,
+            last_window: 0,
+            done: false,
+            carry: TickMeta::default(),
+        })
+    }
+
+    fn end(&self) -> i64 {
+        self.start + self.history.first().map_or(0, Vec::len) as i64
+    }
+
+    /// Start over from `position`, as if the stream had just begun.
+    fn reset(&mut self, channels: usize, position: i64) {
+        self.history = vec![Vec::new(); channels];
+        self.start = position;
+        self.scan = position;
+        self.armed = false;
+        self.holdoff_until = i64::MIN;
+        self.pending = None;
+        self.last_window = position;
+    }
+
+    /// First edge at or after `scan` that may trigger, advancing `scan`.
+    fn find_edge(&mut self) -> Option<i64> {
+        let end = self.end();
+        let ch = self.config.channel.min(self.history.len() - 1);
+        let level = self.config.level;
+        let band = self.config.hysteresis;
+        while self.scan < end {
+            let p = self.scan;
+            self.scan += 1;
+            let s = self.history[ch][(p - self.start) as usize];
+            let (beyond_band, crossed) = match self.config.slope {
+                Slope::Rising => (s < level - band, s >= level),
+                Slope::Falling => (s > level + band, s <= level),
+            };
+            if beyond_band {
+                self.armed = true;
+            } else if self.armed && crossed {
+                // An edge inside the holdoff is spent, not deferred.
+                self.armed = false;
+                if p >= self.holdoff_until && p - self.pre as i64 >= self.start {
+                    return Some(p);
+                }
+            }
+        }
+        None
+    }
+
+    fn window(&mut self, from: i64, flags: u8) -> bool {
+        let at = (from - self.start) as usize;
+        let frames = self.config.frames;
+        let window: Vec<Vec<f32>> = self
+            .history
+            .iter()
+            .map(|h| h[at..at + frames].to_vec())
+            .collect();
+        let mut meta = TickMeta {
+            position: from,
+            ..std::mem::take(&mut self.carry)
+        };
+        meta.flags |= flags;
+        self.last_window = from + frames as i64;
+        self.inner.send(meta, &window)
+    }
+}
+
+impl BufferSink for TriggerSink {
+    fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+        if tick.is_empty() {
+            return true;
+        }
+        if self.history.len() != tick.len() || meta.position != self.end() {
+            self.reset(tick.len(), meta.position);
+        }
+        if self.done {
+            return true;
+        }
+        self.carry.absorb(meta);
+        for (h, plane) in self.history.iter_mut().zip(tick) {
+            h.extend_from_slice(plane);
+        }
+
+        let frames = self.config.frames as i64;
+        loop {
+            if let Some(from) = self.pending {
+                if self.end() < from + frames {
+                    break;
+                }
+                self.pending = None;
+                if !self.window(from, 0) {
+                    return false;
+                }
+                if self.config.mode == TriggerMode::Single {
+                    self.done = true;
+                    self.history.iter_mut().for_each(Vec::clear);
+                    return true;
+                }
+                continue;
+            }
+            if let Some(edge) = self.find_edge() {
+                self.pending = Some(edge - self.pre as i64);
+                self.holdoff_until =
+                    edge + (self.config.holdoff as i64).max(frames - self.pre as i64);
+                continue;
+            }
+            let end = self.end();
+            if self.config.mode == TriggerMode::Auto
+                && end - self.last_window >= self.auto_frames
+                && end - self.start >= frames
+                && !self.window(end - frames, FLAG_AUTO)
+            {
+                return false;
+            }
+            break;
+        }
+
+        // Keep what a pending window or the next edge's pre-trigger part
+        // could still need.
+        let keep = match self.pending {
+            Some(from) => from,
+            None => self.scan - self.pre.max(self.config.frames) as i64,
+        };
+        if keep > self.start {
+            let n = ((keep - self.start) as usize).min(self.history[0].len());
+            self.history.iter_mut().for_each(|h| {
+                h.drain(..n);
+            });
+            self.start += n as i64;
+        }
+        true
+    }
+    fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    type Windows = Arc<Mutex<Vec<(TickMeta, Vec<Vec<f32>>)>>>;
+
+    struct Collect(Windows);
+
+    impl BufferSink for Collect {
+        fn send(&mut self, meta: TickMeta, tick: &[Vec<f32>]) -> bool {
+            self.0.lock().unwrap().push((meta, tick.to_vec()));
+            true
+        }
+        fn close(&mut self) {}
+    }
+
+    fn config(mode: TriggerMode) -> TriggerConfig {
+        TriggerConfig {
+            mode,
+            channel: 0,
+            level: 0.0,
+            slope: Slope::Rising,
+            hysteresis: 0.01,
+            frames: 64,
+            pre_trigger: Some(16),
+            holdoff: 0,
+        }
+    }
+
+    /// Feed `planes` in batches of `batch` frames at 1 kHz; returns the
+    /// windows passed on.
+    fn run(
+        config: TriggerConfig,
+        planes: &[Vec<f32>],
+        batch: usize,
+    ) -> Vec<(TickMeta, Vec<Vec<f32>>)> {
+        let out = Windows::default();
+        let mut sink = TriggerSink::new(Box::new(Collect(out.clone())), config, 1000).unwrap();
+        let len = planes[0].len();
+        for from in (0..len).step_by(batch) {
+            let to = (from + batch).min(len);
+            let tick: Vec<Vec<f32>> = planes.iter().map(|p| p[from..to].to_vec()).collect();
+            let meta = TickMeta {
+                position: from as i64,
+                ..TickMeta::default()
+            };
+            assert!(sink.send(meta, &tick));
+        }
+        let windows = std::mem::take(&mut *out.lock().unwrap());
+        windows
+    }
+
+    /// -1 for 50 frames, then +1 for 50: rising edges at 50, 150, …
+    fn square(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| if i % 100 < 50 { -1.0 } else { 1.0 })
+            .collect()
+    }
+
+    fn positions(windows: &[(TickMeta, Vec<Vec<f32>>)]) -> Vec<i64> {
+        windows.iter().map(|(m, _)| m.position).collect()
+    }
+
+    #[test]
+    fn edge_sits_at_pre_trigger() {
+        // Batches of 37 put edges anywhere in a batch, and windows across
+        // batch boundaries. Triggering on the second channel.
+        let planes = vec![vec![0.0; 1000], square(1000)];
+        let windows = run(
+            TriggerConfig {
+                channel: 1,
+                ..config(TriggerMode::Normal)
+            },
+            &planes,
+            37,
+        );
+        let want: Vec<i64> = (0..10).map(|k| 50 + 100 * k - 16).collect();
+        assert_eq!(positions(&windows), want);
+        for (meta, w) in &windows {
+            assert_eq!(meta.flags, 0);
+            assert_eq!(w.len(), 2);
+            assert_eq!(w[1].len(), 64);
+            assert_eq!((w[1][15], w[1][16]), (-1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn sine_windows_are_in_phase() {
+        let sine: Vec<f32> = (0..2000)
+            .map(|i| (std::f32::consts::TAU * i as f32 / 80.0).sin())
+            .collect();
+        let windows = run(
+            TriggerConfig {
+                level: 0.5,
+                slope: Slope::Falling,
+                ..config(TriggerMode::Normal)
+            },
+            &[sine],
+            50,
+        );
+        assert!(windows.len() > 10);
+        for (meta, w) in &windows {
+            assert!(
+                w[0][15] > 0.5 && w[0][16] <= 0.5,
+                "window at {}",
+                meta.position
+            );
+            assert_eq!((meta.position - windows[0].0.position) % 80, 0);
+        }
+    }
+
+    #[test]
+    fn holdoff_suppresses_edges() {
+        let windows = run(
+            TriggerConfig {
+                holdoff: 150,
+                ..config(TriggerMode::Normal)
+            },
+            &[square(1000)],
+            64,
+        );
+        // Edges at 150, 350, … fall inside the holdoff of the one before.
+        assert_eq!(positions(&windows), [34, 234, 434, 634, 834]);
+    }
+
+    #[test]
+    fn auto_free_runs_on_a_flat_signal() {
+        let flat = vec![vec![0.25; 1000]];
+        assert!(run(config(TriggerMode::Normal), &flat, 40).is_empty());
+
+        // 100 ms at 1 kHz since the last window's end without a trigger:
+        // the latest 64 frames go out, at the first batch boundary past it.
+        let windows = run(config(TriggerMode::Auto), &flat, 40);
+        assert_eq!(positions(&windows), [56, 176, 296, 416, 536, 656, 776, 896]);
+        for (meta, w) in &windows {
+            assert_eq!(meta.flags, FLAG_AUTO);
+            assert_eq!(w[0], [0.25; 64]);
+        }
+
+        // A triggerable signal never free-runs.
+        let windows = run(config(TriggerMode::Auto), &[square(1000)], 40);
+        assert!(windows.iter().all(|(m, _)| m.flags == 0));
+    }
+
+    #[test]
+    fn single_fires_once() {
+        let windows = run(config(TriggerMode::Single), &[square(1000)], 37);
+        assert_eq!(positions(&windows), [34]);
+    }
+
+    #[test]
+    fn bad_configs_are_rejected() {
+        let new = |c| TriggerSink::new(Box::new(Collect(Windows::default())), c, 48_000);
+        let c = config(TriggerMode::Auto);
+        assert!(new(TriggerConfig { frames: 0, ..c }).is_err());
+        assert!(new(TriggerConfig {
+            pre_trigger: Some(65),
+            ..c
+        })
+        .is_err());
+        assert!(new(TriggerConfig {
+            hysteresis: -1.0,
+            ..c
+        })
+        .is_err());
+        assert!(new(TriggerConfig {
+            pre_trigger: None,
+            ..c
+        })
+        .is_ok());
+    }
+}
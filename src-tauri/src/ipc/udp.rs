@@ -1,3 +1,4 @@
+use std::io;
 use std::sync::Arc;
 use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
@@ -65,3 +66,17 @@ impl UdpState {
         Ok(())
     }
 }
+
+/// A `recv` error that only means "no reply this time". On Linux a
+/// `connect()`ed socket reports an ICMP port-unreachable from an earlier
+/// send (scsynth not running) as `ConnectionRefused` on the next `recv`;
+/// pollers keep going through it so they notice scsynth coming back.
+pub fn transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
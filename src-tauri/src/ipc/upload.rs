@@ -0,0 +1,129 @@
+//! Audio file upload into scsynth buffers, for when scsynth runs on another
+//! machine and `/b_allocRead` can't see the file.
+//!
+//! The file is decoded (and resampled to the server's rate) in Rust, the
+//! buffer `/b_alloc`ed, and the interleaved samples sent as `/b_setn`
+//! chunks that keep each datagram under 8 KB, the same bound sclang's
+//! `Buffer.sendCollection` uses. `/b_setn` has no reply of its own, so a
+//! `/sync` after every `SYNC_EVERY` chunks both paces the upload (scsynth's
+//! receive buffer doesn't overflow) and acknowledges what came before it.
+
+use super::scsynth;
+use crate::audio::{decode, resample};
+use rosc::OscType;
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+/// Samples per `/b_setn`: 6.4 KB of floats plus a small header.
+const CHUNK_SAMPLES: usize = 1600;
+/// Chunks in flight before waiting for `/synced`.
+const SYNC_EVERY: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadProgress {
+    pub bufnum: i32,
+    /// Frames acknowledged so far.
+    pub sent: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadInfo {
+    pub bufnum: i32,
+    pub frames: usize,
+    pub channels: usize,
+    /// Rate the samples were written at: the server's if known, else the
+    /// file's.
+    pub sample_rate: u32,
+}
+
+/// Decode `bytes` and write them into `bufnum`, replacing its contents.
+/// `extension` hints the container; `server_sr` is the rate to resample
+/// to, or `None` to keep the file's. `on_progress` is called after every
+/// acknowledged `/sync`.
+pub async fn upload(
+    scsynth_addr: &str,
+    bufnum: i32,
+    bytes: Vec<u8>,
+    extension: Option<String>,
+    server_sr: Option<i32>,
+    mut on_progress: impl FnMut(UploadProgress),
+) -> Result<UploadInfo, String> {
+    let target_sr = server_sr.filter(|sr| *sr > 0).map(|sr| sr as u32);
+    let (planes, sample_rate) = tokio::task::spawn_blocking(move || {
+        let decoded = decode::decode(bytes, extension.as_deref())?;
+        Ok::<_, String>(match target_sr {
+            Some(to) if to != decoded.sample_rate => {
+                eprintln!(
+                    "upload[buf {bufnum}] resampling {} Hz -> {to} Hz",
+                    decoded.sample_rate
+                );
+                (
+                    resample::resample(&decoded.planes, decoded.sample_rate, to),
+                    to,
+                )
+            }
+            _ => (decoded.planes, decoded.sample_rate),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let channels = planes.len();
+    let frames = planes[0].len();
+
+    let sock = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("upload bind failed: {e}"))?;
+    sock.connect(scsynth_addr)
+        .await
+        .map_err(|e| format!("upload connect {scsynth_addr} failed: {e}"))?;
+
+    scsynth::command(
+        &sock,
+        "/b_alloc",
+        vec![
+            OscType::Int(bufnum),
+            OscType::Int(frames as i32),
+            OscType::Int(channels as i32),
+        ],
+    )
+    .await?;
+
+    let chunk_frames = (CHUNK_SAMPLES / channels).max(1);
+    let mut start = 0;
+    let mut unsynced = 0;
+    let mut syncs = 0;
+    while start < frames {
+        let end = (start + chunk_frames).min(frames);
+        let mut args = Vec::with_capacity(3 + (end - start) * channels);
+        args.push(OscType::Int(bufnum));
+        args.push(OscType::Int((start * channels) as i32));
+        args.push(OscType::Int(((end - start) * channels) as i32));
+        for i in start..end {
+            args.extend(planes.iter().map(|p| OscType::Float(p[i])));
+        }
+        scsynth::send(&sock, "/b_setn", args).await?;
+        start = end;
+        unsynced += 1;
+        if unsynced == SYNC_EVERY || start == frames {
+            syncs += 1;
+            scsynth::sync(&sock, syncs).await?;
+            unsynced = 0;
+            on_progress(UploadProgress {
+                bufnum,
+                sent: start,
+                total: frames,
+            });
+        }
+    }
+
+    eprintln!("upload[buf {bufnum}] {frames} frames x {channels} ch @ {sample_rate} Hz");
+    Ok(UploadInfo {
+        bufnum,
+        frames,
+        channels,
+        sample_rate,
+    })
+}
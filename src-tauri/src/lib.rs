@@ -1,6 +1,13 @@
+pub mod audio;
 pub mod cli;
 pub mod clock;
 pub mod config;
 pub mod ipc;
+pub mod meter;
+pub mod nrt;
+pub mod pattern;
 pub mod plugin;
+pub mod record;
+pub mod schedule;
 pub mod server;
+pub mod server_info;
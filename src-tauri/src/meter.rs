@@ -0,0 +1,299 @@
+//! Peak/RMS level meters from `SendPeakRMS`.
+//!
+//! A plugin meters its output with `SendPeakRMS.kr(sig, replyRate, peakLag,
+//! cmdName, replyID)`, which sends `cmdName node replyID peak rms [peak
+//! rms …]` to every notified client. `MeterService` binds a dedicated UDP
+//! socket, registers with `/notify 1` like a `PhaseClock` does for `/tr`,
+//! and keeps one meter per reply id, so the UI gets levels without polling
+//! buffers.
+//!
+//! Ballistics live here rather than in each view: levels rise at once and
+//! fall at most `decay_db` dB per second, and a peak-hold value sticks for
+//! `hold_ms` unless a higher peak replaces it. All values are linear
+//! amplitudes. Updates are batched into one frame per `FRAME_MS` holding
+//! only the meters that changed; a meter whose synth stopped replying for
+//! `STALE_MS` gets a last all-zero entry and is dropped.
+//!
+//! Frames go out on a broadcast channel (`MeterService::subscribe()`,
+//! filtered by reply id): GUI mode forwards them as the `meter-levels` Tauri
+//! event, serve mode on the `/meters` WebSocket. A scsynth restart drops
+//! `/notify` registrations, so the listener re-sends `/notify 1` every
+//! `NOTIFY_MS` instead of relying on the server-info poller; a redundant one
+//! only earns a `/fail` that is ignored.
+
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+
+/// `SendPeakRMS`'s default `cmdName`.
+pub const DEFAULT_CMD: &str = "/reply";
+
+/// One batch of meter updates per ~30 Hz display frame.
+const FRAME_MS: u64 = 33;
+
+const NOTIFY_MS: u64 = 2000;
+
+/// No reply for this long means the metering synth is gone.
+const STALE_MS: u64 = 1000;
+
+/// Backlog of unread frames per subscriber before it starts lagging.
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MeterConfig {
+    /// Address the `SendPeakRMS` UGens reply to.
+    pub cmd_name: String,
+    /// How long a peak-hold value sticks before falling back to the peak.
+    pub hold_ms: u64,
+    /// Fall rate of the peak and RMS levels, in dB per second.
+    pub decay_db: f32,
+}
+
+impl Default for MeterConfig {
+    fn default() -> Self {
+        Self {
+            cmd_name: DEFAULT_CMD.to_string(),
+            hold_ms: 1500,
+            decay_db: 24.0,
+        }
+    }
+}
+
+/// One meter's levels, one entry per channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeterLevels {
+    pub reply_id: i32,
+    pub node: i32,
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    pub hold: Vec<f32>,
+}
+
+/// Ballistics state of one reply id.
+struct Meter {
+    levels: MeterLevels,
+    hold_until: Vec<Instant>,
+    last: Instant,
+    changed: bool,
+}
+
+impl Meter {
+    fn new(reply_id: i32, node: i32, channels: usize, now: Instant) -> Self {
+        Self {
+            levels: MeterLevels {
+                reply_id,
+                node,
+                peak: vec![0.0; channels],
+                rms: vec![0.0; channels],
+                hold: vec![0.0; channels],
+            },
+            hold_until: vec![now; channels],
+            last: now,
+            changed: false,
+        }
+    }
+
+    /// Fold in one reply's `(peak, rms)` pairs.
+    fn update(&mut self, node: i32, pairs: &[(f32, f32)], now: Instant, config: &MeterConfig) {
+        if pairs.len() != self.levels.peak.len() || node != self.levels.node {
+            *self = Meter::new(self.levels.reply_id, node, pairs.len(), now);
+        }
+        let dt = now.duration_since(self.last).as_secs_f32();
+        let fall = 10f32.powf(-config.decay_db * dt / 20.0);
+        let hold_for = Duration::from_millis(config.hold_ms);
+        let l = &mut self.levels;
+        for (c, &(peak, rms)) in pairs.iter().enumerate() {
+            l.peak[c] = peak.max(l.peak[c] * fall);
+            l.rms[c] = rms.max(l.rms[c] * fall);
+            if peak >= l.hold[c] || now >= self.hold_until[c] {
+                l.hold[c] = l.peak[c];
+                self.hold_until[c] = now + hold_for;
+            }
+        }
+        self.last = now;
+        self.changed = true;
+    }
+}
+
+/// A `MeterService::subscribe()` handle that only yields the wanted meters.
+pub struct MeterReceiver {
+    rx: broadcast::Receiver<Vec<MeterLevels>>,
+    reply_ids: Option<HashSet<i32>>,
+}
+
+impl MeterReceiver {
+    /// Next frame with at least one wanted meter in it; `None` once the
+    /// service is gone. A lagging receiver skips the frames it missed.
+    pub async fn recv(&mut self) -> Option<Vec<MeterLevels>> {
+        loop {
+            let mut frame = match self.rx.recv().await {
+                Ok(frame) => frame,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            if let Some(ids) = &self.reply_ids {
+                frame.retain(|m| ids.contains(&m.reply_id));
+            }
+            if !frame.is_empty() {
+                return Some(frame);
+            }
+        }
+    }
+}
+
+pub struct MeterService {
+    events: broadcast::Sender<Vec<MeterLevels>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl MeterService {
+    pub fn new() -> Self {
+        Self {
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Frames carrying the meters in `reply_ids`, or every meter for `None`.
+    pub fn subscribe(&self, reply_ids: Option<HashSet<i32>>) -> MeterReceiver {
+        MeterReceiver {
+            rx: self.events.subscribe(),
+            reply_ids,
+        }
+    }
+
+    /// (Re)bind to `scsynth_addr` and start listening. A running listener
+    /// is replaced, and its meters start over.
+    pub async fn start(&self, scsynth_addr: &str, config: MeterConfig) -> Result<(), String> {
+        if config.decay_db.is_nan() || config.decay_db <= 0.0 {
+            return Err(format!(
+                "meter decay must be > 0 dB/s, got {}",
+                config.decay_db
+            ));
+        }
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+        let sock = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| format!("meter bind failed: {e}"))?;
+        sock.connect(scsynth_addr)
+            .await
+            .map_err(|e| format!("meter connect {scsynth_addr} failed: {e}"))?;
+        let notify = encoder::encode(&OscPacket::Message(OscMessage {
+            addr: "/notify".into(),
+            args: vec![OscType::Int(1)],
+        }))
+        .map_err(|e| e.to_string())?;
+        eprintln!(
+            "meter started on {scsynth_addr}; listening for {}",
+            config.cmd_name
+        );
+
+        let events = self.events.clone();
+        let handle = tokio::spawn(async move {
+            let mut meters: HashMap<i32, Meter> = HashMap::new();
+            let mut buf = [0u8; 4096];
+            let mut frame = tokio::time::interval(Duration::from_millis(FRAME_MS));
+            frame.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut renotify = tokio::time::interval(Duration::from_millis(NOTIFY_MS));
+            renotify.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let stale = Duration::from_millis(STALE_MS);
+            loop {
+                tokio::select! {
+                    _ = renotify.tick() => {
+                        let _ = sock.send(&notify).await;
+                    }
+                    _ = frame.tick() => {
+                        let mut out = Vec::new();
+                        meters.retain(|id, m| {
+                            if m.last.elapsed() > stale {
+                                eprintln!("meter[{id}] stopped");
+                                let n = m.levels.peak.len();
+                                out.push(MeterLevels {
+                                    peak: vec![0.0; n],
+                                    rms: vec![0.0; n],
+                                    hold: vec![0.0; n],
+                                    ..m.levels.clone()
+                                });
+                                return false;
+                            }
+                            if std::mem::take(&mut m.changed) {
+                                out.push(m.levels.clone());
+                            }
+                            true
+                        });
+                        if !out.is_empty() {
+                            let _ = events.send(out);
+                        }
+                    }
+                    r = sock.recv(&mut buf) => {
+                        let Ok(n) = r else { break };
+                        let Ok((_, packet)) = decoder::decode_udp(&buf[..n]) else { continue };
+                        let OscPacket::Message(m) = packet else { continue };
+                        if m.addr != config.cmd_name {
+                            continue;
+                        }
+                        let Some(PeakRms { node, reply_id, pairs }) = parse_peak_rms(&m.args) else {
+                            continue;
+                        };
+                        let now = Instant::now();
+                        let meter = meters.entry(reply_id).or_insert_with(|| {
+                            eprintln!(
+                                "meter[{reply_id}] node {node}, {} channel(s)",
+                                pairs.len()
+                            );
+                            Meter::new(reply_id, node, pairs.len(), now)
+                        });
+                        meter.update(node, &pairs, now, &config);
+                    }
+                }
+            }
+            eprintln!("meter listener exited");
+        });
+        *self.task.lock().await = Some(handle);
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// One `SendPeakRMS` reply.
+struct PeakRms {
+    node: i32,
+    reply_id: i32,
+    pairs: Vec<(f32, f32)>,
+}
+
+/// `node replyID peak rms [peak rms …]`. Anything else sent to the same
+/// address (a plain `SendReply`, say) is skipped.
+fn parse_peak_rms(args: &[OscType]) -> Option<PeakRms> {
+    let [OscType::Int(node), OscType::Int(reply_id), rest @ ..] = args else {
+        return None;
+    };
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = rest
+        .chunks(2)
+        .map(|pair| match pair {
+            [OscType::Float(peak), OscType::Float(rms)] => Some((*peak, *rms)),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(PeakRms {
+        node: *node,
+        reply_id: *reply_id,
+        pairs,
+    })
+}
@@ -0,0 +1,92 @@
+use crate::pattern::{self, SpecFile};
+use clap::Subcommand;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum NrtCommand {
+    /// Render a pattern spec to a score for `scsynth -N`
+    Render {
+        /// Path to spec JSON (`{ "clocks": [...], "patterns": [...] }`)
+        spec: String,
+
+        /// Output score path
+        #[arg(long)]
+        out: String,
+
+        /// Score length in seconds
+        #[arg(long)]
+        duration: f64,
+
+        /// Base RNG seed; pattern N is seeded with `seed + N`
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Directory holding `<synthdef>.scsyndef` files [default: the spec's directory]
+        #[arg(long)]
+        synthdefs: Option<String>,
+
+        /// Sample rate the score is meant to be rendered at
+        #[arg(long, default_value_t = 48000)]
+        sample_rate: u32,
+    },
+}
+
+pub fn run(cmd: NrtCommand) -> Result<(), String> {
+    match cmd {
+        NrtCommand::Render {
+            spec,
+            out,
+            duration,
+            seed,
+            synthdefs,
+            sample_rate,
+        } => cmd_render(
+            Path::new(&spec),
+            Path::new(&out),
+            duration,
+            seed,
+            synthdefs,
+            sample_rate,
+        ),
+    }
+}
+
+fn cmd_render(
+    spec: &Path,
+    out: &Path,
+    duration: f64,
+    seed: Option<u64>,
+    synthdefs: Option<String>,
+    sample_rate: u32,
+) -> Result<(), String> {
+    if sample_rate == 0 {
+        return Err("sample rate must be > 0".to_string());
+    }
+    let file = SpecFile::load(spec)?;
+    let dir = match synthdefs {
+        Some(d) => PathBuf::from(d),
+        None => spec.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+
+    let names: BTreeSet<&str> = file.patterns.iter().map(|p| p.synthdef.as_str()).collect();
+    let mut defs = Vec::with_capacity(names.len());
+    for name in names {
+        let path = dir.join(format!("{name}.scsyndef"));
+        let bytes = std::fs::read(&path)
+            .map_err(|e| format!("Error reading synthdef \"{}\": {e}", path.display()))?;
+        defs.push(bytes);
+    }
+
+    let entries = pattern::render::render(&file, &defs, duration, seed)?;
+    let f = std::fs::File::create(out)
+        .map_err(|e| format!("Error creating \"{}\": {e}", out.display()))?;
+    super::write(&entries, std::io::BufWriter::new(f))?;
+
+    println!("Wrote {} bundles to {}", entries.len(), out.display());
+    println!(
+        "Render with: scsynth -N {} _ out.wav {sample_rate} WAV int24",
+        out.display()
+    );
+    Ok(())
+}
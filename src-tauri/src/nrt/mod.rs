@@ -0,0 +1,188 @@
+//! scsynth non-realtime (NRT) score files.
+//!
+//! `scsynth -N` reads a flat sequence of OSC bundles, each prefixed by its
+//! byte length as a big-endian int32. Bundle timetags are *seconds from
+//! the start of the render*, not NTP wall-clock time: `seconds` holds the
+//! whole part, `fractional` the remainder in units of 2^-32 s. Rendering
+//! stops after the last bundle, so a score conventionally ends with a
+//! no-op bundle at the desired duration.
+
+pub mod cli;
+
+use rosc::{decoder, encoder, OscBundle, OscPacket, OscTime};
+use std::io::{Read, Write};
+
+pub struct ScoreEntry {
+    /// Seconds from the start of the render.
+    pub time: f64,
+    pub content: Vec<OscPacket>,
+}
+
+pub fn osc_time_from_secs(secs: f64) -> OscTime {
+    let secs = secs.max(0.0);
+    let whole = secs.floor();
+    let frac = ((secs - whole) * 4_294_967_296.0)
+        .round()
+        .min(u32::MAX as f64);
+    OscTime {
+        seconds: whole as u32,
+        fractional: frac as u32,
+    }
+}
+
+pub fn secs_from_osc_time(t: OscTime) -> f64 {
+    t.seconds as f64 + t.fractional as f64 / 4_294_967_296.0
+}
+
+/// Write `entries` as a score. Entries are written in the order given;
+/// callers are responsible for sorting them by time.
+pub fn write(entries: &[ScoreEntry], mut w: impl Write) -> Result<(), String> {
+    for entry in entries {
+        let bundle = OscBundle {
+            timetag: osc_time_from_secs(entry.time),
+            content: entry.content.clone(),
+        };
+        let bytes = encoder::encode(&OscPacket::Bundle(bundle)).map_err(|e| e.to_string())?;
+        w.write_all(&(bytes.len() as i32).to_be_bytes())
+            .and_then(|_| w.write_all(&bytes))
+            .map_err(|e| format!("Error writing score: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Read a score back. Fails on truncated input or on a top-level packet
+/// that isn't a bundle.
+pub fn read(mut r: impl Read) -> Result<Vec<ScoreEntry>, String> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)
+        .map_err(|e| format!("Error reading score: {e}"))?;
+
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let header = data
+            .get(pos..pos + 4)
+            .ok_or_else(|| format!("Truncated length prefix at byte {pos}"))?;
+        let len = i32::from_be_bytes(header.try_into().unwrap());
+        if len < 0 {
+            return Err(format!("Negative bundle length at byte {pos}"));
+        }
+        pos += 4;
+        let bytes = data
+            .get(pos..pos + len as usize)
+            .ok_or_else(|| format!("Truncated bundle at byte {pos}"))?;
+        pos += len as usize;
+        match decoder::decode_udp(bytes) {
+            Ok((_, OscPacket::Bundle(b))) => entries.push(ScoreEntry {
+                time: secs_from_osc_time(b.timetag),
+                content: b.content,
+            }),
+            Ok((_, OscPacket::Message(m))) => {
+                return Err(format!("Expected bundle, found message {}", m.addr))
+            }
+            Err(e) => return Err(format!("Invalid OSC in score: {e}")),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{render::render, SpecFile};
+    use rosc::{OscMessage, OscType};
+
+    const SPEC: &str = r#"{
+        "clocks": [{"id": "main", "bpm": 120, "playing": true}],
+        "patterns": [{"id": "p", "clock": "main", "synthdef": "beep", "group": 0, "keys": {
+            "freq": {"type": "value", "value": 440},
+            "dur": {"type": "value", "value": 0.5},
+            "legato": {"type": "value", "value": 0.5}
+        }}]
+    }"#;
+
+    fn messages(entry: &ScoreEntry) -> Vec<&OscMessage> {
+        entry
+            .content
+            .iter()
+            .map(|p| match p {
+                OscPacket::Message(m) => m,
+                OscPacket::Bundle(_) => panic!("nested bundle in score"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rendered_score_round_trips() {
+        let file: SpecFile = serde_json::from_str(SPEC).unwrap();
+        let synthdef = b"SCgf\0\0\0\x02".to_vec();
+        let entries = render(&file, &[synthdef.clone()], 1.0, None).unwrap();
+        let mut bytes = Vec::new();
+        write(&entries, &mut bytes).unwrap();
+        let back = read(&bytes[..]).unwrap();
+
+        // Half a beat at 120 bpm is 0.25 s; every note is released 0.125 s
+        // after it starts, and the end marker sits at the duration.
+        let times: Vec<f64> = back.iter().map(|e| e.time).collect();
+        assert_eq!(
+            times,
+            [0.0, 0.125, 0.25, 0.375, 0.5, 0.625, 0.75, 0.875, 1.0]
+        );
+
+        let first = messages(&back[0]);
+        assert_eq!(first[0].addr, "/d_recv");
+        assert_eq!(first[0].args, [OscType::Blob(synthdef)]);
+
+        let nodes: Vec<i32> = back
+            .iter()
+            .flat_map(messages)
+            .filter(|m| m.addr == "/s_new")
+            .map(|m| match m.args[1] {
+                OscType::Int(n) => n,
+                ref other => panic!("node id {other:?}"),
+            })
+            .collect();
+        assert_eq!(nodes, [1000, 1001, 1002, 1003]);
+
+        let last = messages(back.last().unwrap());
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].addr, "/c_set");
+        assert_eq!(last[0].args, [OscType::Int(0), OscType::Float(0.0)]);
+
+        let mut again = Vec::new();
+        write(&back, &mut again).unwrap();
+        assert_eq!(again, bytes);
+    }
+
+    #[test]
+    fn timetags_are_seconds_from_start() {
+        let t = osc_time_from_secs(2.5);
+        assert_eq!((t.seconds, t.fractional), (2, 1 << 31));
+        assert_eq!(secs_from_osc_time(t), 2.5);
+        let third = secs_from_osc_time(osc_time_from_secs(1.0 / 3.0));
+        assert!((third - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(osc_time_from_secs(-1.0).seconds, 0);
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        let entries = [ScoreEntry {
+            time: 0.0,
+            content: vec![],
+        }];
+        let mut bytes = Vec::new();
+        write(&entries, &mut bytes).unwrap();
+        assert!(read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read(&bytes[..2]).is_err());
+        assert!(read(&(-1i32).to_be_bytes()[..]).is_err());
+
+        let msg = encoder::encode(&OscPacket::Message(OscMessage {
+            addr: "/status".into(),
+            args: vec![],
+        }))
+        .unwrap();
+        let mut bytes = (msg.len() as i32).to_be_bytes().to_vec();
+        bytes.extend(msg);
+        assert!(read(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use super::{PatternState, SpecFile};
+use crate::schedule::{self, Scheduler};
+use clap::Subcommand;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Subcommand)]
+pub enum PatternCommand {
+    /// Play a pattern spec headlessly until interrupted
+    Run {
+        /// Path to spec JSON (`{ "clocks": [...], "patterns": [...] }`)
+        spec: String,
+
+        /// scsynth UDP address
+        #[arg(long, default_value = "127.0.0.1:57110", env = "SC_SCSYNTH_ADDR")]
+        scsynth: String,
+
+        /// Stop after this many seconds instead of waiting for Ctrl-C
+        #[arg(long)]
+        duration: Option<f64>,
+
+        /// Base RNG seed; pattern N is seeded with `seed + N`
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Scheduling latency for timetagged bundles, in milliseconds
+        #[arg(long, default_value_t = schedule::DEFAULT_LATENCY_MS, env = "SC_LATENCY_MS")]
+        latency_ms: u64,
+    },
+}
+
+pub fn run(cmd: PatternCommand) -> Result<(), String> {
+    match cmd {
+        PatternCommand::Run {
+            spec,
+            scsynth,
+            duration,
+            seed,
+            latency_ms,
+        } => {
+            let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+            rt.block_on(cmd_run(
+                Path::new(&spec),
+                &scsynth,
+                duration,
+                seed,
+                Duration::from_millis(latency_ms),
+            ))
+        }
+    }
+}
+
+/// Every pattern in the file is started regardless of its `run` flag —
+/// the point of the command is to play the spec.
+async fn cmd_run(
+    path: &Path,
+    scsynth: &str,
+    duration: Option<f64>,
+    seed: Option<u64>,
+    latency: Duration,
+) -> Result<(), String> {
+    let file = SpecFile::load(path)?;
+    if file.patterns.is_empty() {
+        return Err("Spec contains no patterns".to_string());
+    }
+
+    let state = PatternState::new(Arc::new(Scheduler::new(latency)));
+    for clock in file.clocks {
+        state.clock_upsert(clock).await?;
+    }
+    for (i, mut pattern) in file.patterns.into_iter().enumerate() {
+        if let Some(s) = seed {
+            pattern.seed = Some(s.wrapping_add(i as u64));
+        }
+        pattern.run = true;
+        state.pattern_upsert(pattern, scsynth).await?;
+    }
+
+    println!("Playing {} on {scsynth}", path.display());
+    match duration {
+        Some(secs) => {
+            let limit = Duration::from_secs_f64(secs.max(0.0));
+            tokio::select! {
+                _ = tokio::time::sleep(limit) => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        None => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+
+    for id in state.pattern_ids().await {
+        state.pattern_remove(&id).await?;
+    }
+    println!("Stopped.");
+    Ok(())
+}
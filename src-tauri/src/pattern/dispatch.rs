@@ -0,0 +1,51 @@
+use crate::schedule::Scheduler;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::time::Instant;
+use tokio::net::UdpSocket;
+
+/// Bind an ephemeral socket connected to scsynth. Patterns send from their
+/// own socket rather than the frontend's, so playback doesn't depend on a
+/// client being bound.
+pub(super) async fn connect(scsynth_addr: &str) -> Result<UdpSocket, String> {
+    let sock = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("pattern bind failed: {e}"))?;
+    sock.connect(scsynth_addr)
+        .await
+        .map_err(|e| format!("pattern connect {scsynth_addr} failed: {e}"))?;
+    Ok(sock)
+}
+
+/// `/s_new <synthdef> <node> 0 <group> key value …` — added at the head of
+/// `group`. Live playback passes `node = -1` (auto-assigned).
+pub fn s_new(synthdef: &str, node: i32, group: i32, controls: &[(String, f64)]) -> OscMessage {
+    let mut args = Vec::with_capacity(4 + controls.len() * 2);
+    args.push(OscType::String(synthdef.to_string()));
+    args.push(OscType::Int(node));
+    args.push(OscType::Int(0));
+    args.push(OscType::Int(group));
+    for (key, value) in controls {
+        args.push(OscType::String(key.clone()));
+        args.push(OscType::Float(*value as f32));
+    }
+    OscMessage {
+        addr: "/s_new".into(),
+        args,
+    }
+}
+
+/// Send the event as a bundle timetagged for its logical time `at`, so
+/// timer jitter is absorbed by the scheduler's latency.
+pub(super) async fn send_s_new(
+    sock: &UdpSocket,
+    scheduler: &Scheduler,
+    at: Instant,
+    synthdef: &str,
+    group: i32,
+    controls: &[(String, f64)],
+) {
+    let msg = s_new(synthdef, -1, group, controls);
+    let _ = scheduler
+        .send_at(sock, at, vec![OscPacket::Message(msg)])
+        .await;
+}
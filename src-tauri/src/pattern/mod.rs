@@ -0,0 +1,17 @@
+//! Backend pattern sequencer (`<sc-clock>` / `<sc-pattern>`).
+//!
+//! Each pattern runs as its own tokio task that `sleep_until`s the next
+//! event on its tempo clock and fires `/s_new` from a Rust-owned UDP socket.
+//! Because nothing here depends on a browser timer, sequences keep playing
+//! when the tab is backgrounded or (in serve mode) disconnected. See
+//! NOTES.md §2 for the design.
+
+pub mod cli;
+mod dispatch;
+pub mod render;
+mod state;
+mod stream;
+mod task;
+
+pub use state::{ClockSpec, PatternSpec, PatternState, SpecFile};
+pub use stream::StreamSpec;
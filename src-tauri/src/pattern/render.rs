@@ -0,0 +1,134 @@
+//! Offline evaluation of a spec file into an NRT score.
+//!
+//! Same evaluator as live playback, but driven by beat arithmetic instead
+//! of timers: each pattern starts at beat 0 of its clock, every clock is
+//! treated as playing at its spec'd bpm, and RNGs are always seeded so the
+//! same spec renders the same score byte-for-byte.
+
+use super::dispatch;
+use super::state::{Pattern, SpecFile};
+use super::stream::Rng;
+use crate::nrt::ScoreEntry;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::collections::BTreeSet;
+
+/// Node ids below this are left alone (root group, default group, and
+/// anything the spec's own groups might use).
+const FIRST_NODE: i32 = 1000;
+
+/// Guard against specs that never advance (all-zero deltas).
+const MAX_EVENTS: usize = 1_000_000;
+
+/// Evaluate `file` for `duration` seconds. At time zero the score loads
+/// every blob in `synthdefs` (`/d_recv`) and creates the groups patterns
+/// target; then each event becomes a `/s_new` with an explicit node id.
+/// Events with a `sustain` get `/n_set <node> gate 0` at release, or an
+/// `/n_free` at the end if release falls past `duration`. A final `/c_set`
+/// marks the end of the render.
+///
+/// `seed = Some(s)` seeds pattern N with `s + N`, overriding the spec;
+/// otherwise a pattern uses its own `seed`, falling back to N.
+pub fn render(
+    file: &SpecFile,
+    synthdefs: &[Vec<u8>],
+    duration: f64,
+    seed: Option<u64>,
+) -> Result<Vec<ScoreEntry>, String> {
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err("duration must be > 0".to_string());
+    }
+
+    let mut timeline: Vec<(f64, OscMessage)> = Vec::new();
+    for bytes in synthdefs {
+        timeline.push((0.0, message("/d_recv", vec![OscType::Blob(bytes.clone())])));
+    }
+    let groups: BTreeSet<i32> = file.patterns.iter().map(|p| p.group).collect();
+    for group in groups.into_iter().filter(|g| *g != 0) {
+        let args = vec![OscType::Int(group), OscType::Int(0), OscType::Int(0)];
+        timeline.push((0.0, message("/g_new", args)));
+    }
+
+    let mut next_node = FIRST_NODE;
+    let mut events = 0usize;
+    for (i, spec) in file.patterns.iter().enumerate() {
+        let bpm = file
+            .clocks
+            .iter()
+            .find(|c| c.id == spec.clock)
+            .map(|c| c.bpm)
+            .ok_or_else(|| format!("pattern \"{}\": unknown clock \"{}\"", spec.id, spec.clock))?;
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return Err(format!("clock \"{}\": bpm must be > 0", spec.clock));
+        }
+        let secs_per_beat = 60.0 / bpm;
+        let pattern_seed = match seed {
+            Some(s) => s.wrapping_add(i as u64),
+            None => spec.seed.unwrap_or(i as u64),
+        };
+        let mut pattern = Pattern::new(spec, Rng::seeded(pattern_seed));
+
+        let mut beat = 0.0;
+        loop {
+            let t = beat * secs_per_beat;
+            if t >= duration {
+                break;
+            }
+            let Some(event) = pattern.next_event() else {
+                break;
+            };
+            events += 1;
+            if events > MAX_EVENTS {
+                return Err(format!(
+                    "more than {MAX_EVENTS} events; does a pattern never advance?"
+                ));
+            }
+
+            let node = next_node;
+            next_node += 1;
+            timeline.push((
+                t,
+                dispatch::s_new(&spec.synthdef, node, spec.group, &event.controls),
+            ));
+            if let Some(sustain) = event.sustain {
+                let release = t + sustain * secs_per_beat;
+                if release < duration {
+                    let args = vec![
+                        OscType::Int(node),
+                        OscType::String("gate".into()),
+                        OscType::Float(0.0),
+                    ];
+                    timeline.push((release, message("/n_set", args)));
+                } else {
+                    timeline.push((duration, message("/n_free", vec![OscType::Int(node)])));
+                }
+            }
+            beat += event.delta;
+        }
+    }
+
+    let end = vec![OscType::Int(0), OscType::Float(0.0)];
+    timeline.push((duration, message("/c_set", end)));
+
+    // Stable sort: same-time commands keep insertion order, so /d_recv and
+    // /g_new precede the first /s_new and the end marker stays last.
+    timeline.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut entries: Vec<ScoreEntry> = Vec::new();
+    for (time, msg) in timeline {
+        match entries.last_mut() {
+            Some(last) if last.time == time => last.content.push(OscPacket::Message(msg)),
+            _ => entries.push(ScoreEntry {
+                time,
+                content: vec![OscPacket::Message(msg)],
+            }),
+        }
+    }
+    Ok(entries)
+}
+
+fn message(addr: &str, args: Vec<OscType>) -> OscMessage {
+    OscMessage {
+        addr: addr.into(),
+        args,
+    }
+}
@@ -0,0 +1,475 @@
+use super::dispatch;
+use super::stream::{Rng, Stream, StreamSpec};
+use super::task::{pattern_task, Ctrl};
+use crate::schedule::Scheduler;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+pub type ClockId = String;
+pub type PatternId = String;
+
+/// Tempo clock. Beats are accumulated piecewise: `beats_at_start` holds
+/// everything counted before the current play segment, `started_at` marks
+/// when that segment began. Every bpm change or pause folds the running
+/// segment into `beats_at_start`, so `current_beats()` stays continuous and
+/// monotonic across any sequence of transitions.
+pub struct TempoClock {
+    bpm: f64,
+    playing: bool,
+    /// `None` while paused.
+    started_at: Option<Instant>,
+    beats_at_start: f64,
+}
+
+impl TempoClock {
+    pub fn new(bpm: f64, playing: bool) -> Self {
+        Self {
+            bpm,
+            playing,
+            started_at: playing.then(Instant::now),
+            beats_at_start: 0.0,
+        }
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    pub fn current_beats(&self) -> f64 {
+        self.beats_at(Instant::now())
+    }
+
+    fn beats_at(&self, now: Instant) -> f64 {
+        match (self.playing, self.started_at) {
+            (true, Some(t0)) => {
+                self.beats_at_start
+                    + now.saturating_duration_since(t0).as_secs_f64() * self.bpm / 60.0
+            }
+            _ => self.beats_at_start,
+        }
+    }
+
+    pub fn set_playing(&mut self, on: bool) {
+        match (self.playing, on) {
+            (false, true) => {
+                self.started_at = Some(Instant::now());
+                self.playing = true;
+            }
+            (true, false) => {
+                self.beats_at_start = self.current_beats();
+                self.started_at = None;
+                self.playing = false;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        let now = Instant::now();
+        self.beats_at_start = self.beats_at(now);
+        self.started_at = self.playing.then_some(now);
+        self.bpm = bpm;
+    }
+
+    /// Wall-clock instant at which `beat` falls under the current tempo.
+    /// `None` while paused. Beats already behind the clock map to the start
+    /// of the running segment, i.e. "fire now".
+    pub fn instant_at(&self, beat: f64) -> Option<Instant> {
+        let t0 = self.started_at.filter(|_| self.playing)?;
+        let secs = ((beat - self.beats_at_start) * 60.0 / self.bpm).max(0.0);
+        Some(t0 + Duration::from_secs_f64(secs))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockSpec {
+    pub id: ClockId,
+    pub bpm: f64,
+    pub playing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatternSpec {
+    pub id: PatternId,
+    pub clock: ClockId,
+    /// Synthdef name; every event is a fresh `/s_new` of it.
+    pub synthdef: String,
+    /// Target group for `/s_new` (added at head).
+    #[serde(default = "default_group")]
+    pub group: i32,
+    #[serde(default)]
+    pub run: bool,
+    /// One stream per key. Ordered so evaluation (and therefore RNG draw
+    /// order) is deterministic for a given spec.
+    pub keys: BTreeMap<String, StreamSpec>,
+    /// RNG seed for `rand`/`white` streams; random when omitted.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_group() -> i32 {
+    1
+}
+
+/// A spec file as loaded by the CLI: clocks first, then the patterns that
+/// run on them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecFile {
+    #[serde(default)]
+    pub clocks: Vec<ClockSpec>,
+    pub patterns: Vec<PatternSpec>,
+}
+
+impl SpecFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading \"{}\": {e}", path.display()))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid pattern spec: {e}"))
+    }
+}
+
+/// One evaluated event: the controls to send and how far (in beats) to
+/// advance before the next one.
+pub struct Event {
+    pub controls: Vec<(String, f64)>,
+    pub delta: f64,
+    /// Beats until the note is released (`/n_set gate 0`), if the spec
+    /// asked for one. Only honoured by NRT rendering, which owns node ids;
+    /// live events use auto-assigned ids and must free themselves.
+    pub sustain: Option<f64>,
+}
+
+pub(super) struct Pattern {
+    pub(super) id: PatternId,
+    pub(super) clock: ClockId,
+    pub(super) synthdef: String,
+    pub(super) group: i32,
+    pub(super) run: bool,
+    /// Clock beat at which the next event fires.
+    pub(super) next_beat: f64,
+    streams: Vec<(String, Stream)>,
+    rng: Rng,
+}
+
+impl Pattern {
+    pub(super) fn new(spec: &PatternSpec, rng: Rng) -> Self {
+        Self {
+            id: spec.id.clone(),
+            clock: spec.clock.clone(),
+            synthdef: spec.synthdef.clone(),
+            group: spec.group,
+            run: spec.run,
+            next_beat: 0.0,
+            streams: build_streams(spec),
+            rng,
+        }
+    }
+
+    /// Pull one value from every stream. `None` once any stream runs out.
+    /// `dur`, `delta`, `legato`, `sustain` and `stretch` are consumed by the
+    /// engine (sclang event convention), not sent as controls. A missing
+    /// `dur` defaults to one beat; `delta` overrides `dur`; both are scaled
+    /// by `stretch`. Negative deltas are clamped to zero. `sustain` (or
+    /// `dur * legato`) is only set when the spec has one of those keys.
+    pub(super) fn next_event(&mut self) -> Option<Event> {
+        let mut controls = Vec::with_capacity(self.streams.len());
+        let mut dur = 1.0;
+        let mut delta = None;
+        let mut stretch = 1.0;
+        let mut legato = None;
+        let mut sustain = None;
+        for (key, stream) in self.streams.iter_mut() {
+            let v = stream.next(&mut self.rng)?;
+            match key.as_str() {
+                "dur" => dur = v,
+                "delta" => delta = Some(v),
+                "stretch" => stretch = v,
+                "legato" => legato = Some(v),
+                "sustain" => sustain = Some(v),
+                _ => controls.push((key.clone(), v)),
+            }
+        }
+        let delta = (delta.unwrap_or(dur) * stretch).max(0.0);
+        if !delta.is_finite() {
+            return None;
+        }
+        let sustain = sustain
+            .or_else(|| legato.map(|l| dur * l))
+            .map(|s| (s * stretch).max(0.0));
+        Some(Event {
+            controls,
+            delta,
+            sustain,
+        })
+    }
+}
+
+fn build_streams(spec: &PatternSpec) -> Vec<(String, Stream)> {
+    spec.keys
+        .iter()
+        .map(|(k, s)| (k.clone(), Stream::new(s)))
+        .collect()
+}
+
+struct PatternHandle {
+    pattern: Arc<Mutex<Pattern>>,
+    control: mpsc::Sender<Ctrl>,
+    task: JoinHandle<()>,
+    /// scsynth the task sends to.
+    scsynth_addr: String,
+}
+
+/// Backend-owned sequencer. Holds the named tempo clocks and one tokio task
+/// per pattern; tasks fire timetagged `/s_new` bundles over a socket owned
+/// by this state, so playback is independent of any frontend being alive.
+pub struct PatternState {
+    clocks: Arc<Mutex<HashMap<ClockId, TempoClock>>>,
+    patterns: Mutex<HashMap<PatternId, PatternHandle>>,
+    /// `(scsynth addr, connected socket)`; re-bound if the address changes.
+    udp: Mutex<Option<(String, Arc<UdpSocket>)>>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl PatternState {
+    pub fn new(scheduler: Arc<Scheduler>) -> Self {
+        Self {
+            clocks: Arc::new(Mutex::new(HashMap::new())),
+            patterns: Mutex::new(HashMap::new()),
+            udp: Mutex::new(None),
+            scheduler,
+        }
+    }
+
+    /// Create or update a tempo clock. Updates preserve beat position:
+    /// bpm and play/pause transitions are folded into the clock's running
+    /// beat count, and every pattern on the clock is woken to reschedule.
+    pub async fn clock_upsert(&self, spec: ClockSpec) -> Result<(), String> {
+        if !spec.bpm.is_finite() || spec.bpm <= 0.0 {
+            return Err(format!("clock \"{}\": bpm must be > 0", spec.id));
+        }
+        {
+            let mut clocks = self.clocks.lock().await;
+            match clocks.get_mut(&spec.id) {
+                Some(c) => {
+                    if c.bpm() != spec.bpm {
+                        c.set_bpm(spec.bpm);
+                    }
+                    c.set_playing(spec.playing);
+                }
+                None => {
+                    clocks.insert(spec.id.clone(), TempoClock::new(spec.bpm, spec.playing));
+                }
+            }
+        }
+        self.wake_clock(&spec.id).await;
+        Ok(())
+    }
+
+    /// Create or replace a pattern. An existing pattern keeps its task,
+    /// RNG and beat position; its streams restart from the new spec, and
+    /// a new `scsynth_addr` moves it to that server. A pattern whose
+    /// streams ran out is created afresh.
+    pub async fn pattern_upsert(
+        &self,
+        spec: PatternSpec,
+        scsynth_addr: &str,
+    ) -> Result<(), String> {
+        let now_beats = self
+            .clocks
+            .lock()
+            .await
+            .get(&spec.clock)
+            .map(|c| c.current_beats())
+            .ok_or_else(|| format!("pattern \"{}\": unknown clock \"{}\"", spec.id, spec.clock))?;
+
+        let mut patterns = self.patterns.lock().await;
+        if let Some(h) = patterns.get_mut(&spec.id).filter(|h| !h.task.is_finished()) {
+            if h.scsynth_addr != scsynth_addr {
+                let udp = self.socket(scsynth_addr).await?;
+                h.control
+                    .send(Ctrl::Rebind(udp))
+                    .await
+                    .map_err(|_| format!("pattern \"{}\" has stopped", spec.id))?;
+                eprintln!("pattern[{}] moved to {scsynth_addr}", spec.id);
+                h.scsynth_addr = scsynth_addr.to_string();
+            }
+            {
+                let mut p = h.pattern.lock().await;
+                if (spec.run && !p.run) || p.clock != spec.clock {
+                    p.next_beat = now_beats;
+                }
+                p.clock = spec.clock.clone();
+                p.synthdef = spec.synthdef.clone();
+                p.group = spec.group;
+                p.run = spec.run;
+                p.streams = build_streams(&spec);
+            }
+            let _ = h.control.try_send(Ctrl::Wake);
+            return Ok(());
+        }
+
+        let udp = self.socket(scsynth_addr).await?;
+        let rng = spec.seed.map(Rng::seeded).unwrap_or_else(Rng::from_entropy);
+        let mut pattern = Pattern::new(&spec, rng);
+        pattern.next_beat = now_beats;
+        let pattern = Arc::new(Mutex::new(pattern));
+        let (control, rx) = mpsc::channel(8);
+        let task = tokio::spawn(pattern_task(
+            self.clocks.clone(),
+            pattern.clone(),
+            rx,
+            udp,
+            self.scheduler.clone(),
+        ));
+        eprintln!("pattern[{}] created on clock \"{}\"", spec.id, spec.clock);
+        patterns.insert(
+            spec.id,
+            PatternHandle {
+                pattern,
+                control,
+                task,
+                scsynth_addr: scsynth_addr.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    pub async fn pattern_ids(&self) -> Vec<PatternId> {
+        self.patterns.lock().await.keys().cloned().collect()
+    }
+
+    /// Stop a pattern's task and forget it.
+    pub async fn pattern_remove(&self, id: &str) -> Result<(), String> {
+        let h = self
+            .patterns
+            .lock()
+            .await
+            .remove(id)
+            .ok_or_else(|| format!("unknown pattern \"{id}\""))?;
+        let _ = h.control.send(Ctrl::Remove).await;
+        let _ = h.task.await;
+        eprintln!("pattern[{id}] removed");
+        Ok(())
+    }
+
+    /// Start or stop a pattern. Starting schedules the next event on the
+    /// clock's current beat rather than replaying anything missed. A
+    /// pattern whose streams ran out can't be started again; upsert it.
+    pub async fn pattern_set_run(&self, id: &str, run: bool) -> Result<(), String> {
+        let patterns = self.patterns.lock().await;
+        let h = patterns
+            .get(id)
+            .ok_or_else(|| format!("unknown pattern \"{id}\""))?;
+        if run && h.task.is_finished() {
+            return Err(format!(
+                "pattern \"{id}\" has finished; upsert it to start over"
+            ));
+        }
+        {
+            let mut p = h.pattern.lock().await;
+            if run && !p.run {
+                let clocks = self.clocks.lock().await;
+                if let Some(c) = clocks.get(&p.clock) {
+                    p.next_beat = c.current_beats();
+                }
+            }
+            p.run = run;
+        }
+        let _ = h.control.try_send(Ctrl::Wake);
+        Ok(())
+    }
+
+    async fn wake_clock(&self, clock: &str) {
+        let patterns = self.patterns.lock().await;
+        for h in patterns.values() {
+            if h.pattern.lock().await.clock == clock {
+                let _ = h.control.try_send(Ctrl::Wake);
+            }
+        }
+    }
+
+    async fn socket(&self, scsynth_addr: &str) -> Result<Arc<UdpSocket>, String> {
+        let mut guard = self.udp.lock().await;
+        if let Some((addr, sock)) = guard.as_ref() {
+            if addr == scsynth_addr {
+                return Ok(sock.clone());
+            }
+        }
+        let sock = Arc::new(dispatch::connect(scsynth_addr).await?);
+        *guard = Some((scsynth_addr.to_string(), sock.clone()));
+        Ok(sock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 600 bpm and one event per beat: an `/s_new` every 100 ms.
+    fn spec(id: &str, keys: &str) -> (ClockSpec, PatternSpec) {
+        let clock = serde_json::from_str(r#"{"id": "c", "bpm": 600, "playing": true}"#).unwrap();
+        let pattern = serde_json::from_str(&format!(
+            r#"{{"id": "{id}", "clock": "c", "synthdef": "beep", "run": true, "keys": {keys}}}"#
+        ))
+        .unwrap();
+        (clock, pattern)
+    }
+
+    async fn listener() -> (UdpSocket, String) {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = sock.local_addr().unwrap().to_string();
+        (sock, addr)
+    }
+
+    async fn received(sock: &UdpSocket) -> bool {
+        let mut buf = [0u8; 1024];
+        tokio::time::timeout(Duration::from_secs(2), sock.recv(&mut buf))
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn upsert_moves_a_live_pattern_to_a_new_server() {
+        let state = PatternState::new(Arc::new(Scheduler::new(Duration::ZERO)));
+        let (a, a_addr) = listener().await;
+        let (b, b_addr) = listener().await;
+        let (clock, pattern) = spec("p", r#"{"freq": {"type": "value", "value": 440}}"#);
+        state.clock_upsert(clock).await.unwrap();
+        state
+            .pattern_upsert(pattern.clone(), &a_addr)
+            .await
+            .unwrap();
+        assert!(received(&a).await);
+
+        state.pattern_upsert(pattern, &b_addr).await.unwrap();
+        assert!(received(&b).await);
+        state.pattern_remove("p").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn finished_patterns_must_be_upserted_to_restart() {
+        let state = PatternState::new(Arc::new(Scheduler::new(Duration::ZERO)));
+        let (sock, addr) = listener().await;
+        let once =
+            r#"{"freq": {"type": "seq", "values": [{"type": "value", "value": 1}], "repeat": 1}}"#;
+        let (clock, pattern) = spec("p", once);
+        state.clock_upsert(clock).await.unwrap();
+        state.pattern_upsert(pattern.clone(), &addr).await.unwrap();
+        assert!(received(&sock).await);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert!(state.pattern_set_run("p", true).await.is_err());
+        assert!(state.pattern_set_run("p", false).await.is_ok());
+        state.pattern_upsert(pattern, &addr).await.unwrap();
+        assert!(received(&sock).await);
+    }
+}
@@ -0,0 +1,161 @@
+//! Value streams — the Rust side of `<sc-pseq>`, `<sc-prand>` & co.
+//!
+//! A `StreamSpec` is the serde shape the frontend (or a spec file) sends;
+//! `Stream` is the stateful evaluator built from it. Each call to `next()`
+//! yields the value for one event, or `None` once a finite stream is
+//! exhausted — which ends the pattern that owns it.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StreamSpec {
+    /// Constant.
+    Value { value: f64 },
+    /// Cycles through `values` in order; `repeat = None` cycles forever.
+    Seq {
+        values: Vec<StreamSpec>,
+        #[serde(default)]
+        repeat: Option<u32>,
+    },
+    /// Uniform-random child pick per event.
+    Rand { values: Vec<StreamSpec> },
+    /// Uniform random float in `[low, high)`.
+    White { low: f64, high: f64 },
+    /// Arithmetic series `start, start + step, …`.
+    Series { start: f64, step: f64 },
+    /// Geometric series `start, start * ratio, …`.
+    Geom { start: f64, ratio: f64 },
+}
+
+pub enum Stream {
+    Value(f64),
+    Seq {
+        values: Vec<Stream>,
+        cursor: usize,
+        repeat: Option<u32>,
+        cycles: u32,
+    },
+    Rand {
+        values: Vec<Stream>,
+    },
+    White {
+        low: f64,
+        high: f64,
+    },
+    Series {
+        step: f64,
+        next: f64,
+    },
+    Geom {
+        ratio: f64,
+        next: f64,
+    },
+}
+
+impl Stream {
+    pub fn new(spec: &StreamSpec) -> Self {
+        match spec {
+            StreamSpec::Value { value } => Stream::Value(*value),
+            StreamSpec::Seq { values, repeat } => Stream::Seq {
+                values: values.iter().map(Stream::new).collect(),
+                cursor: 0,
+                repeat: *repeat,
+                cycles: 0,
+            },
+            StreamSpec::Rand { values } => Stream::Rand {
+                values: values.iter().map(Stream::new).collect(),
+            },
+            StreamSpec::White { low, high } => Stream::White {
+                low: *low,
+                high: *high,
+            },
+            StreamSpec::Series { start, step } => Stream::Series {
+                step: *step,
+                next: *start,
+            },
+            StreamSpec::Geom { start, ratio } => Stream::Geom {
+                ratio: *ratio,
+                next: *start,
+            },
+        }
+    }
+
+    /// Value for the next event. Children of `Seq`/`Rand` yield one value
+    /// per visit (sclang's `Pseq` would embed them fully; for the MVP set
+    /// one-per-visit is what the markup reads as).
+    pub fn next(&mut self, rng: &mut Rng) -> Option<f64> {
+        match self {
+            Stream::Value(v) => Some(*v),
+            Stream::Seq {
+                values,
+                cursor,
+                repeat,
+                cycles,
+            } => {
+                if values.is_empty() || repeat.is_some_and(|r| *cycles >= r) {
+                    return None;
+                }
+                let v = values[*cursor].next(rng);
+                *cursor += 1;
+                if *cursor == values.len() {
+                    *cursor = 0;
+                    *cycles += 1;
+                }
+                v
+            }
+            Stream::Rand { values } => {
+                if values.is_empty() {
+                    return None;
+                }
+                let i = rng.below(values.len());
+                values[i].next(rng)
+            }
+            Stream::White { low, high } => Some(*low + rng.next_f64() * (*high - *low)),
+            Stream::Series { step, next } => {
+                let v = *next;
+                *next += *step;
+                Some(v)
+            }
+            Stream::Geom { ratio, next } => {
+                let v = *next;
+                *next *= *ratio;
+                Some(v)
+            }
+        }
+    }
+}
+
+/// SplitMix64. Small, fast, and seedable so a pattern can be replayed
+/// exactly — `getrandom` is only used to pick the seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn from_entropy() -> Self {
+        let mut bytes = [0u8; 8];
+        let _ = getrandom::getrandom(&mut bytes);
+        Self(u64::from_le_bytes(bytes))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `0..n`. `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
@@ -0,0 +1,79 @@
+use super::dispatch;
+use super::state::{ClockId, Pattern, TempoClock};
+use crate::schedule::Scheduler;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, Mutex};
+
+pub(super) enum Ctrl {
+    /// Something the schedule depends on changed (bpm, play state, run
+    /// flag, spec); recompute the next wake-up.
+    Wake,
+    /// Send to a different scsynth from now on.
+    Rebind(Arc<UdpSocket>),
+    Remove,
+}
+
+/// One task per pattern. Event-driven: each iteration computes the instant
+/// of the next event from the pattern's beat and its clock's tempo, then
+/// waits on either that instant or a control message. A paused clock or a
+/// stopped pattern has no next instant, so the task sleeps until woken.
+/// Events go out as bundles timetagged `latency` past their logical time.
+pub(super) async fn pattern_task(
+    clocks: Arc<Mutex<HashMap<ClockId, TempoClock>>>,
+    pattern: Arc<Mutex<Pattern>>,
+    mut ctrl: mpsc::Receiver<Ctrl>,
+    mut udp: Arc<UdpSocket>,
+    scheduler: Arc<Scheduler>,
+) {
+    let id = pattern.lock().await.id.clone();
+    loop {
+        let next_at = {
+            let p = pattern.lock().await;
+            if p.run {
+                clocks
+                    .lock()
+                    .await
+                    .get(&p.clock)
+                    .and_then(|c| c.instant_at(p.next_beat))
+            } else {
+                None
+            }
+        };
+
+        tokio::select! {
+            at = sleep_until(next_at) => {
+                let (synthdef, group, event) = {
+                    let mut p = pattern.lock().await;
+                    let Some(event) = p.next_event() else {
+                        eprintln!("pattern[{id}] streams exhausted; stopping");
+                        break;
+                    };
+                    p.next_beat += event.delta;
+                    (p.synthdef.clone(), p.group, event)
+                };
+                dispatch::send_s_new(&udp, &scheduler, at, &synthdef, group, &event.controls)
+                    .await;
+            }
+            msg = ctrl.recv() => match msg {
+                Some(Ctrl::Wake) => {}
+                Some(Ctrl::Rebind(sock)) => udp = sock,
+                Some(Ctrl::Remove) | None => break,
+            }
+        }
+    }
+}
+
+/// Resolves with the target instant — the event's logical time, which is
+/// what gets timetagged, not the (jittery) moment the timer woke us.
+async fn sleep_until(at: Option<Instant>) -> Instant {
+    match at {
+        Some(t) => {
+            tokio::time::sleep_until(t.into()).await;
+            t
+        }
+        None => std::future::pending().await,
+    }
+}
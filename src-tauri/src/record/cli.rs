@@ -0,0 +1,86 @@
+use super::{RecordOptions, Recorder};
+use crate::server_info;
+use clap::Args;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct RecordArgs {
+    /// Output WAV path
+    #[arg(long)]
+    out: String,
+
+    /// First audio bus to record (0 = first hardware output)
+    #[arg(long, default_value_t = 0)]
+    bus: i32,
+
+    /// Number of consecutive buses to record
+    #[arg(long, default_value_t = 2)]
+    channels: i32,
+
+    /// scsynth UDP address
+    #[arg(long, default_value = "127.0.0.1:57110", env = "SC_SCSYNTH_ADDR")]
+    scsynth: String,
+
+    /// Stop after this many seconds instead of waiting for Ctrl-C
+    #[arg(long)]
+    duration: Option<f64>,
+
+    /// Capture buffer number; must be free on the server
+    #[arg(long, default_value_t = 1023)]
+    bufnum: i32,
+
+    /// Capture buffer length in frames
+    #[arg(long, default_value_t = 32768)]
+    frames: i32,
+
+    /// Frames per `/b_getn`
+    #[arg(long, default_value_t = 512)]
+    chunk: i32,
+}
+
+pub fn run(args: RecordArgs) -> Result<(), String> {
+    let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    rt.block_on(cmd_record(args))
+}
+
+async fn cmd_record(args: RecordArgs) -> Result<(), String> {
+    let status = server_info::query_status(&args.scsynth, Duration::from_secs(2)).await?;
+    let sample_rate = status.sample_rate();
+    let path = PathBuf::from(&args.out);
+    let recorder = Recorder::start(RecordOptions {
+        scsynth_addr: args.scsynth.clone(),
+        bus: args.bus,
+        channels: args.channels,
+        bufnum: args.bufnum,
+        frames: args.frames,
+        chunk: args.chunk,
+        sample_rate,
+        path: path.clone(),
+    })
+    .await?;
+
+    println!(
+        "Recording bus {}..{} from {} to {} at {sample_rate} Hz",
+        args.bus,
+        args.bus + args.channels - 1,
+        args.scsynth,
+        path.display()
+    );
+    match args.duration {
+        Some(secs) => {
+            let limit = Duration::from_secs_f64(secs.max(0.0));
+            tokio::select! {
+                _ = tokio::time::sleep(limit) => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        None => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+
+    recorder.stop().await?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
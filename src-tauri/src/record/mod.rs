@@ -0,0 +1,148 @@
+//! Headless bus recording (`sc-app record`).
+//!
+//! No new streaming machinery: the command allocates a buffer, starts a
+//! dedicated phase clock whose owned broadcaster also captures the bus into
+//! that buffer off its own Phasor (`clock::Capture`), and subscribes a WAV
+//! `FileSink` to the buffer in clocked mode. The reader then follows a
+//! writer whose position it knows exactly, the same catch-up loop the
+//! scopes and GUI recordings use. Stopping unsubscribes (finalising the
+//! file), stops the clock (freeing the node) and frees the buffer.
+
+pub mod cli;
+
+use crate::audio::Format;
+use crate::clock::{AnchorSource, Broadcaster, Capture, ClockConfig, ClockService};
+use crate::ipc::buffer::{BufferStreamState, FileSink, SubId};
+use crate::ipc::scsynth;
+use rosc::OscType;
+use std::path::PathBuf;
+use tokio::net::UdpSocket;
+
+/// `SendTrig` ids for capture clocks are `TRIGGER_BASE + bufnum`, clear of
+/// `CLOCK_TRIGGER_ID` and of each other for the default 1024 buffers.
+const TRIGGER_BASE: i32 = 5000;
+
+pub struct RecordOptions {
+    pub scsynth_addr: String,
+    /// First audio bus to record.
+    pub bus: i32,
+    pub channels: i32,
+    pub bufnum: i32,
+    /// Capture buffer length; the capture Phasor wraps at this.
+    pub frames: i32,
+    pub chunk: i32,
+    pub sample_rate: i32,
+    pub path: PathBuf,
+}
+
+/// A running recording: the buffer, its capture clock and the file reader.
+pub struct Recorder {
+    sock: UdpSocket,
+    clock: ClockService,
+    clock_id: String,
+    streams: BufferStreamState,
+    sub_id: SubId,
+    bufnum: i32,
+}
+
+impl Recorder {
+    pub async fn start(opts: RecordOptions) -> Result<Self, String> {
+        if opts.channels < 1 {
+            return Err(format!("channels must be >= 1, got {}", opts.channels));
+        }
+        if opts.frames < 2 * opts.chunk {
+            return Err(format!(
+                "frames ({}) must be at least twice chunk ({})",
+                opts.frames, opts.chunk
+            ));
+        }
+        let sock = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| format!("record bind failed: {e}"))?;
+        sock.connect(&opts.scsynth_addr)
+            .await
+            .map_err(|e| format!("record connect {} failed: {e}", opts.scsynth_addr))?;
+
+        scsynth::command(
+            &sock,
+            "/b_alloc",
+            vec![
+                OscType::Int(opts.bufnum),
+                OscType::Int(opts.frames),
+                OscType::Int(opts.channels),
+            ],
+        )
+        .await?;
+        eprintln!(
+            "record[buf {}] allocated {} frames x {} channel(s)",
+            opts.bufnum, opts.frames, opts.channels
+        );
+
+        let clock = ClockService::new();
+        let clock_id = format!("record-{}", opts.bufnum);
+        let config = ClockConfig {
+            scsynth_addr: opts.scsynth_addr.clone(),
+            source: AnchorSource::Trigger {
+                trigger_id: TRIGGER_BASE + opts.bufnum,
+            },
+            frames: opts.frames as i64,
+            sample_rate: opts.sample_rate,
+            broadcaster: Some(Broadcaster {
+                phase_bus: None,
+                capture: Some(Capture {
+                    bufnum: opts.bufnum,
+                    bus: opts.bus,
+                    channels: opts.channels,
+                }),
+            }),
+        };
+        let streams = BufferStreamState::new();
+        let started = async {
+            clock.start(&clock_id, config).await?;
+            let sink = Box::new(FileSink::create(
+                &opts.path,
+                Format::Wav,
+                opts.channels,
+                opts.sample_rate,
+            )?);
+            streams
+                .subscribe(
+                    opts.bufnum,
+                    opts.frames,
+                    opts.channels,
+                    opts.chunk,
+                    opts.sample_rate,
+                    &opts.scsynth_addr,
+                    Some(clock.clock(&clock_id).await),
+                    sink,
+                )
+                .await
+        }
+        .await;
+        match started {
+            Ok(sub) => Ok(Self {
+                sock,
+                clock,
+                clock_id,
+                streams,
+                sub_id: sub.id,
+                bufnum: opts.bufnum,
+            }),
+            Err(e) => {
+                clock.stop(&clock_id).await;
+                let _ = scsynth::command(&sock, "/b_free", vec![OscType::Int(opts.bufnum)]).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Finalise the file, free the capture synth, then the buffer it
+    /// writes to.
+    pub async fn stop(self) -> Result<(), String> {
+        self.streams.unsubscribe(self.sub_id).await;
+        self.clock.stop(&self.clock_id).await;
+        scsynth::command(&self.sock, "/b_free", vec![OscType::Int(self.bufnum)]).await?;
+        eprintln!("record[buf {}] freed", self.bufnum);
+        Ok(())
+    }
+}
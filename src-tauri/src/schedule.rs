@@ -0,0 +1,119 @@
+//! Latency-compensated, timetagged OSC dispatch.
+//!
+//! Anything the backend times with tokio (`sleep_until`, intervals) fires
+//! with ~1 ms of jitter. Sent as a plain message, that jitter lands in the
+//! audio. Wrapping the message in a `#bundle` timetagged `latency` ahead of
+//! the event's *logical* time hands the timing to scsynth instead, which
+//! executes it sample-accurately — the same trick as sclang's `s.latency`.
+//! The timer then only needs to fire within `latency` of its target, not
+//! exactly on it.
+//!
+//! Logical times are `Instant`s (what the timer was aiming for, not when it
+//! actually woke up), mapped onto the NTP timescale through `SystemTime`.
+//! Phase-clock consumers can map a `PhaseClock` virtual sample position
+//! to a timetag via `timetag_at_samples`.
+
+use crate::clock::PhaseClock;
+use rosc::{encoder, OscBundle, OscPacket, OscTime};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+/// Same default as sclang's `s.latency`.
+pub const DEFAULT_LATENCY_MS: u64 = 200;
+
+/// OSC's reserved "execute immediately" timetag.
+const IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
+pub struct Scheduler {
+    latency: Duration,
+}
+
+impl Scheduler {
+    pub fn new(latency: Duration) -> Self {
+        Self { latency }
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Timetag for an event whose logical time is `at`: `at + latency`.
+    pub fn timetag(&self, at: Instant) -> OscTime {
+        osc_time(instant_to_system(at) + self.latency())
+    }
+
+    /// Timetag for an event at `at_ms` milliseconds since the Unix epoch
+    /// (JS `Date.now()` scale), plus latency.
+    pub fn timetag_unix_ms(&self, at_ms: f64) -> OscTime {
+        let at = UNIX_EPOCH + Duration::from_secs_f64(at_ms.max(0.0) / 1000.0);
+        osc_time(at + self.latency())
+    }
+
+    /// Absolute timetag for the moment the phase clock's writer reaches
+    /// virtual sample `samples`. No latency is added — the position is
+    /// already an absolute target — so callers must schedule at least
+    /// `latency()` ahead of it. `None` unless the clock is running.
+    ///
+    /// The anchor is stamped at `/tr` receipt, so the mapping runs late by
+    /// the scsynth → us UDP delay (sub-millisecond on loopback).
+    pub async fn timetag_at_samples(&self, clock: &PhaseClock, samples: i64) -> Option<OscTime> {
+        clock
+            .instant_at(samples)
+            .await
+            .map(|at| osc_time(instant_to_system(at)))
+    }
+
+    /// Encode `content` as one bundle timetagged for logical time `at`.
+    pub fn bundle(&self, at: Instant, content: Vec<OscPacket>) -> Result<Vec<u8>, String> {
+        let bundle = OscBundle {
+            timetag: self.timetag(at),
+            content,
+        };
+        encoder::encode(&OscPacket::Bundle(bundle)).map_err(|e| e.to_string())
+    }
+
+    /// Send `content` over a connected socket as a bundle for logical time `at`.
+    pub async fn send_at(
+        &self,
+        sock: &UdpSocket,
+        at: Instant,
+        content: Vec<OscPacket>,
+    ) -> Result<usize, String> {
+        let bytes = self.bundle(at, content)?;
+        sock.send(&bytes).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Wrap already-encoded OSC packets (messages or bundles) in a `#bundle`
+/// without decoding them. Elements must be valid OSC, i.e. 4-byte aligned.
+pub fn bundle_raw(timetag: OscTime, elements: &[&[u8]]) -> Vec<u8> {
+    let len = 16 + elements.iter().map(|e| 4 + e.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(len);
+    out.extend_from_slice(b"#bundle\0");
+    out.extend_from_slice(&timetag.seconds.to_be_bytes());
+    out.extend_from_slice(&timetag.fractional.to_be_bytes());
+    for e in elements {
+        out.extend_from_slice(&(e.len() as u32).to_be_bytes());
+        out.extend_from_slice(e);
+    }
+    out
+}
+
+/// `Instant` has no fixed epoch; translate through the current offset
+/// between the monotonic and system clocks.
+fn instant_to_system(at: Instant) -> SystemTime {
+    let now_i = Instant::now();
+    let now_s = SystemTime::now();
+    if at >= now_i {
+        now_s + (at - now_i)
+    } else {
+        now_s - (now_i - at)
+    }
+}
+
+fn osc_time(t: SystemTime) -> OscTime {
+    OscTime::try_from(t).unwrap_or(IMMEDIATELY)
+}
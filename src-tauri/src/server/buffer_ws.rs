@@ -1,5 +1,7 @@
-use crate::clock::ClockService;
-use crate::ipc::buffer::{BufferStreamState, WsSink};
+use crate::clock::PhaseClock;
+use crate::ipc::buffer::{BufferStreamState, StreamMode, SubId, WsSink};
+use crate::ipc::trigger::{Slope, TriggerConfig, TriggerMode};
+use crate::server_info::ServerInfoService;
 use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
 use http_body_util::Full;
@@ -10,12 +12,16 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
 
+/// `/buffer/{bufnum}`, or `/buffer/{a},{b},…` for a group read in lockstep
+/// (`BufferStreamState::subscribe_group`); the config frame's bufnum is then
+/// the first of the group.
 pub fn handle_ws_upgrade(
     req: Request<Incoming>,
-    bufnum: i32,
+    bufnums: Vec<i32>,
     scsynth_addr: &str,
     state: Arc<BufferStreamState>,
-    clock: Arc<ClockService>,
+    clock: Arc<PhaseClock>,
+    server_info: Arc<ServerInfoService>,
 ) -> Response<Full<Bytes>> {
     let key = match req.headers().get("sec-websocket-key") {
         Some(k) => k.as_bytes().to_vec(),
@@ -33,7 +39,9 @@ pub fn handle_ws_upgrade(
 
     tokio::spawn(async move {
         match hyper::upgrade::on(req).await {
-            Ok(upgraded) => handle_ws_connection(upgraded, bufnum, addr, state, clock).await,
+            Ok(upgraded) => {
+                handle_ws_connection(upgraded, bufnums, addr, state, clock, server_info).await
+            }
             Err(e) => eprintln!("Buffer WS upgrade error: {e}"),
         }
     });
@@ -49,10 +57,11 @@ pub fn handle_ws_upgrade(
 
 async fn handle_ws_connection(
     upgraded: hyper::upgrade::Upgraded,
-    bufnum: i32,
+    bufnums: Vec<i32>,
     scsynth_addr: String,
     state: Arc<BufferStreamState>,
-    clock: Arc<ClockService>,
+    clock: Arc<PhaseClock>,
+    server_info: Arc<ServerInfoService>,
 ) {
     let ws = tokio_tungstenite::WebSocketStream::from_raw_socket(
         TokioIo::new(upgraded),
@@ -63,12 +72,24 @@ async fn handle_ws_connection(
 
     let (mut ws_sink, mut ws_stream) = ws.split();
 
-    // Config frame layout (20 bytes, all i32 LE):
-    //   [0..4]   bufnum            (must match the URL-derived `bufnum`)
-    //   [4..8]   chunk
+    // Config frame layout (20 to 64 bytes, i32 LE unless noted):
+    //   [0..4]   bufnum            (must match the URL's, or its first)
+    //   [4..8]   chunk             (frames)
     //   [8..12]  frames
     //   [12..16] sampleRate
     //   [16..20] phaseTracked      (0 = wall-clock, 1 = clocked)
+    //   [20..24] channels          (optional, default 1)
+    //   [24..28] mode              (optional; 0 = raw, 1 = peak)
+    //   [28..32] pointsPerSecond   (optional, peak mode only)
+    //   [32..36] trigger mode      (optional; 0 = off, 1 auto, 2 normal, 3 single)
+    //   [36..40] trigger channel
+    //   [40..44] trigger level     (f32)
+    //   [44..48] hysteresis        (f32)
+    //   [48..52] slope             (0 = rising, 1 = falling)
+    //   [52..56] window frames
+    //   [56..60] preTrigger        (< 0 = half the window)
+    //   [60..64] holdoff           (frames)
+    // Answered by one text frame; see `send_subscription`.
     let config = match ws_stream.next().await {
         Some(Ok(Message::Binary(data))) if data.len() >= 20 => data,
         _ => return,
@@ -76,35 +97,78 @@ async fn handle_ws_connection(
     let client_bufnum = i32::from_le_bytes(config[0..4].try_into().unwrap());
     let chunk = i32::from_le_bytes(config[4..8].try_into().unwrap());
     let frames = i32::from_le_bytes(config[8..12].try_into().unwrap());
-    let sample_rate = i32::from_le_bytes(config[12..16].try_into().unwrap());
+    // The client's rate is only a fallback for when scsynth hasn't answered
+    // `/status` yet.
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or_else(|| i32::from_le_bytes(config[12..16].try_into().unwrap()));
     let phase_tracked = i32::from_le_bytes(config[16..20].try_into().unwrap()) != 0;
+    let channels = config
+        .get(20..24)
+        .map_or(1, |b| i32::from_le_bytes(b.try_into().unwrap()));
+    let mode = match config.get(24..28).map(|b| i32::from_le_bytes(b.try_into().unwrap())) {
+        Some(1) => StreamMode::Peak,
+        _ => StreamMode::Raw,
+    };
+    let points_per_second = config
+        .get(28..32)
+        .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+        .filter(|&n| n > 0)
+        .map(|n| n as u32);
+
+    let trigger = parse_trigger(&config);
 
-    if client_bufnum != bufnum {
-        eprintln!("Buffer WS: bufnum mismatch (url {bufnum}, config {client_bufnum})");
+    if bufnums.first() != Some(&client_bufnum) {
+        eprintln!("Buffer WS: bufnum mismatch (url {bufnums:?}, config {client_bufnum})");
         return;
     }
 
     let (tx, mut rx) = mpsc::channel::<Message>(4);
-    let sink = Box::new(WsSink { tx });
-    let clock_opt = if phase_tracked { Some(clock) } else { None };
-    let sub_id = match state
-        .subscribe(
-            bufnum,
-            frames,
-            chunk,
-            sample_rate,
-            &scsynth_addr,
-            clock_opt,
-            sink,
-        )
-        .await
-    {
-        Ok(id) => id,
+    let sink = match mode.wrap(
+        Box::new(WsSink::new(tx)),
+        sample_rate,
+        points_per_second,
+        trigger,
+    ) {
+        Ok(sink) => sink,
         Err(e) => {
-            eprintln!("Buffer WS subscribe failed: {e}");
+            let _ = send_subscription::<_, ()>(&mut ws_sink, Err(e)).await;
             return;
         }
     };
+    let clock_opt = if phase_tracked { Some(clock) } else { None };
+    let sub_id = if let [bufnum] = bufnums[..] {
+        let sub = state
+            .subscribe(
+                bufnum,
+                frames,
+                channels,
+                chunk,
+                sample_rate,
+                &scsynth_addr,
+                clock_opt,
+                sink,
+            )
+            .await;
+        send_subscription(&mut ws_sink, sub.map(|s| (s.id, s))).await
+    } else {
+        let sub = state
+            .subscribe_group(
+                bufnums,
+                frames,
+                channels,
+                chunk,
+                sample_rate,
+                &scsynth_addr,
+                clock_opt,
+                sink,
+            )
+            .await;
+        send_subscription(&mut ws_sink, sub.map(|s| (s.id, s))).await
+    };
+    let Some(sub_id) = sub_id else {
+        return;
+    };
 
     // Forward outbound ticks from the reader to the WS client.
     let mut pump = tokio::spawn(async move {
@@ -131,3 +195,60 @@ async fn handle_ws_connection(
 
     state.unsubscribe(sub_id).await;
 }
+
+/// Trigger settings from bytes 32..64 of the config frame, if present and
+/// not off.
+fn parse_trigger(config: &[u8]) -> Option<TriggerConfig> {
+    let word = |at: usize| {
+        config
+            .get(at..at + 4)
+            .map(|b| <[u8; 4]>::try_from(b).unwrap())
+    };
+    let int = |at: usize| word(at).map(i32::from_le_bytes);
+    let mode = match int(32)? {
+        1 => TriggerMode::Auto,
+        2 => TriggerMode::Normal,
+        3 => TriggerMode::Single,
+        _ => return None,
+    };
+    Some(TriggerConfig {
+        mode,
+        channel: int(36)?.max(0) as usize,
+        level: f32::from_le_bytes(word(40)?),
+        hysteresis: f32::from_le_bytes(word(44)?),
+        slope: if int(48)? == 1 {
+            Slope::Falling
+        } else {
+            Slope::Rising
+        },
+        frames: int(52)?.max(0) as usize,
+        pre_trigger: usize::try_from(int(56)?).ok(),
+        holdoff: int(60)?.max(0) as usize,
+    })
+}
+
+/// Answer the config frame with one text frame, before any samples:
+/// `{"subscription": …}` (for buffers, the id and the reader configuration
+/// actually used), or `{"error": …}` followed by a close. Returns the id on
+/// success.
+pub(super) async fn send_subscription<S, T>(
+    ws_sink: &mut S,
+    sub: Result<(SubId, T), String>,
+) -> Option<SubId>
+where
+    S: futures_util::Sink<Message> + Unpin,
+    T: serde::Serialize,
+{
+    let (reply, id) = match sub {
+        Ok((id, sub)) => (serde_json::json!({ "subscription": sub }), Some(id)),
+        Err(e) => {
+            eprintln!("WS subscribe failed: {e}");
+            (serde_json::json!({ "error": e }), None)
+        }
+    };
+    let _ = ws_sink.send(Message::Text(reply.to_string().into())).await;
+    if id.is_none() {
+        let _ = ws_sink.close().await;
+    }
+    id
+}
@@ -0,0 +1,93 @@
+use crate::clock::{ClockEvent, ClockService};
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+/// `/clock`: every `ClockService` transition as a JSON text frame, the
+/// serve-mode counterpart of the `clock-state` Tauri event. Inbound frames
+/// are ignored.
+pub fn handle_ws_upgrade(
+    req: Request<Incoming>,
+    clock: Arc<ClockService>,
+) -> Response<Full<Bytes>> {
+    let key = match req.headers().get("sec-websocket-key") {
+        Some(k) => k.as_bytes().to_vec(),
+        None => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("content-type", "text/plain")
+                .body(Full::new(Bytes::from("Missing Sec-WebSocket-Key")))
+                .unwrap()
+        }
+    };
+
+    let accept = tokio_tungstenite::tungstenite::handshake::derive_accept_key(&key);
+    // Subscribe before the upgrade completes so nothing published in
+    // between is missed.
+    let rx = clock.subscribe();
+
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(upgraded) => handle_ws_connection(upgraded, rx).await,
+            Err(e) => eprintln!("Clock WS upgrade error: {e}"),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .header("connection", "Upgrade")
+        .header("sec-websocket-accept", accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+async fn handle_ws_connection(
+    upgraded: hyper::upgrade::Upgraded,
+    mut rx: broadcast::Receiver<ClockEvent>,
+) {
+    let ws = tokio_tungstenite::WebSocketStream::from_raw_socket(
+        TokioIo::new(upgraded),
+        tokio_tungstenite::tungstenite::protocol::Role::Server,
+        None,
+    )
+    .await;
+
+    let (mut ws_sink, mut ws_stream) = ws.split();
+
+    let mut pump = tokio::spawn(async move {
+        loop {
+            let ev = match rx.recv().await {
+                Ok(ev) => ev,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Ok(json) = serde_json::to_string(&ev) else {
+                continue;
+            };
+            if ws_sink.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Drain inbound frames; exit when the client closes.
+    let mut drain = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if matches!(msg, Message::Close(_)) {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut pump => drain.abort(),
+        _ = &mut drain => pump.abort(),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::meter::MeterReceiver;
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio_tungstenite::tungstenite::Message;
+
+/// `/meters`: every `MeterService` frame as a JSON text frame (an array of
+/// `MeterLevels`), the serve-mode counterpart of the `meter-levels` Tauri
+/// event. Inbound frames are ignored.
+pub fn handle_ws_upgrade(req: Request<Incoming>, rx: MeterReceiver) -> Response<Full<Bytes>> {
+    let key = match req.headers().get("sec-websocket-key") {
+        Some(k) => k.as_bytes().to_vec(),
+        None => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("content-type", "text/plain")
+                .body(Full::new(Bytes::from("Missing Sec-WebSocket-Key")))
+                .unwrap()
+        }
+    };
+
+    let accept = tokio_tungstenite::tungstenite::handshake::derive_accept_key(&key);
+
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(upgraded) => handle_ws_connection(upgraded, rx).await,
+            Err(e) => eprintln!("Meters WS upgrade error: {e}"),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .header("connection", "Upgrade")
+        .header("sec-websocket-accept", accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+async fn handle_ws_connection(upgraded: hyper::upgrade::Upgraded, mut rx: MeterReceiver) {
+    let ws = tokio_tungstenite::WebSocketStream::from_raw_socket(
+        TokioIo::new(upgraded),
+        tokio_tungstenite::tungstenite::protocol::Role::Server,
+        None,
+    )
+    .await;
+
+    let (mut ws_sink, mut ws_stream) = ws.split();
+
+    let mut pump = tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let Ok(json) = serde_json::to_string(&frame) else {
+                continue;
+            };
+            if ws_sink.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Drain inbound frames; exit when the client closes.
+    let mut drain = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if matches!(msg, Message::Close(_)) {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut pump => drain.abort(),
+        _ = &mut drain => pump.abort(),
+    }
+}
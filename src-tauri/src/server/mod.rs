@@ -1,19 +1,32 @@
 mod buffer_ws;
+mod bus_ws;
+mod clock_ws;
+mod meters_ws;
+mod scope_ws;
+mod spectrum_ws;
 mod ws_bridge;
 
-use crate::clock::ClockService;
+use crate::clock::{Broadcaster, ClockConfig, ClockService, DEFAULT_CLOCK, PHASE_BUS};
 use crate::ipc::buffer::BufferStreamState;
+use crate::ipc::control::{ControlSource, ControlSpec, ControlStreamState, DEFAULT_RATE};
+use crate::ipc::recording::{RecordingSpec, RecordingState};
+use crate::ipc::{download, upload};
+use crate::meter::{MeterConfig, MeterService};
+use crate::pattern::{ClockSpec, PatternSpec, PatternState};
+use crate::schedule::Scheduler;
+use crate::server_info::ServerInfoService;
 use crate::{config, plugin};
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
 use hyper::body::Incoming;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Request, Response, StatusCode};
+use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 
 struct AppState {
@@ -21,17 +34,23 @@ struct AppState {
     data_dir: PathBuf,
     scsynth_addr: String,
     buffer_streams: Arc<BufferStreamState>,
+    control_streams: Arc<ControlStreamState>,
+    recordings: RecordingState,
     clock: Arc<ClockService>,
+    meters: Arc<MeterService>,
+    server_info: Arc<ServerInfoService>,
+    patterns: PatternState,
 }
 
-pub fn serve(context: tauri::Context, port: u16, scsynth_addr: String) {
+pub fn serve(context: tauri::Context, port: u16, scsynth_addr: String, latency: Duration) {
     let data_dir = config::data_dir().expect("failed to resolve app data dir");
 
     println!("Serving on http://localhost:{port}");
     println!("scsynth target: {scsynth_addr}");
+    println!("scheduling latency: {} ms", latency.as_millis());
 
     let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
-    if let Err(e) = rt.block_on(run(context, data_dir, port, scsynth_addr)) {
+    if let Err(e) = rt.block_on(run(context, data_dir, port, scsynth_addr, latency)) {
         eprintln!("Server error: {e}");
         std::process::exit(1);
     }
@@ -42,23 +61,43 @@ async fn run(
     data_dir: PathBuf,
     port: u16,
     scsynth_addr: String,
+    latency: Duration,
 ) -> Result<(), String> {
-    // Start the shared clock service eagerly with a default 48 kHz. If the
-    // actual scsynth runs at a different rate the <100 ms between /tr
-    // re-anchors keeps the extrapolation error well inside the safety band;
-    // we don't need to plumb the real rate in until beat-level consumers
-    // arrive in a later phase.
+    // The global clock listens from the start at a provisional 48 kHz; the
+    // server-info poller restarts it with the server's real sample rate once
+    // scsynth answers `/status`, and again whenever scsynth comes back. It
+    // owns its broadcaster synth, so the clock runs with no browser open.
     let clock = Arc::new(ClockService::new());
-    if let Err(e) = clock.start(&scsynth_addr, 48_000).await {
+    let mut clock_config = ClockConfig::global(&scsynth_addr, 48_000);
+    clock_config.broadcaster = Some(Broadcaster {
+        phase_bus: Some(PHASE_BUS),
+        capture: None,
+    });
+    if let Err(e) = clock.start(DEFAULT_CLOCK, clock_config).await {
         eprintln!("Clock start failed: {e}");
     }
+    let meters = Arc::new(MeterService::new());
+    if let Err(e) = meters.start(&scsynth_addr, MeterConfig::default()).await {
+        eprintln!("Meter start failed: {e}");
+    }
+    let server_info = Arc::new(ServerInfoService::new());
+    if let Err(e) = server_info.start(&scsynth_addr, Some(clock.clone())).await {
+        eprintln!("Server info start failed: {e}");
+    }
+
+    let scheduler = Arc::new(Scheduler::new(latency));
 
     let state = Arc::new(AppState {
         context,
         data_dir,
         scsynth_addr,
         buffer_streams: Arc::new(BufferStreamState::new()),
+        control_streams: Arc::new(ControlStreamState::new()),
+        recordings: RecordingState::new(),
         clock,
+        meters,
+        server_info,
+        patterns: PatternState::new(scheduler),
     });
 
     let addr: SocketAddr = format!("0.0.0.0:{port}")
@@ -111,14 +150,73 @@ async fn handle_request(
         .unwrap_or(false);
 
     if is_ws_upgrade {
+        if path == "/clock" {
+            return Ok(clock_ws::handle_ws_upgrade(req, state.clock.clone()));
+        }
+        if path == "/meters" {
+            // `?ids=1,2,…` limits the stream to those reply ids.
+            let ids = query_param(&req, "ids")
+                .map(|ids| ids.split(',').filter_map(|id| id.trim().parse().ok()).collect());
+            return Ok(meters_ws::handle_ws_upgrade(req, state.meters.subscribe(ids)));
+        }
         if let Some(rest) = path.strip_prefix("/buffer/") {
-            if let Ok(bufnum) = rest.parse::<i32>() {
+            // `/buffer/3,4` reads a group of buffers in lockstep.
+            let bufnums: Result<Vec<i32>, _> = rest.split(',').map(str::parse).collect();
+            if let Ok(bufnums) = bufnums {
+                // `?clock=<id>` picks the phase clock a phase-tracked reader
+                // follows; default the global one.
+                let clock_id = query_param(&req, "clock").unwrap_or_else(|| DEFAULT_CLOCK.into());
+                let clock = state.clock.clock(&clock_id).await;
                 return Ok(buffer_ws::handle_ws_upgrade(
+                    req,
+                    bufnums,
+                    &state.scsynth_addr,
+                    state.buffer_streams.clone(),
+                    clock,
+                    state.server_info.clone(),
+                ));
+            }
+        }
+        if let Some(rest) = path.strip_prefix("/spectrum/") {
+            if let Ok(bufnum) = rest.parse::<i32>() {
+                return Ok(spectrum_ws::handle_ws_upgrade(
                     req,
                     bufnum,
                     &state.scsynth_addr,
                     state.buffer_streams.clone(),
                     state.clock.clone(),
+                    state.server_info.clone(),
+                ));
+            }
+        }
+        if let Some(rest) = path.strip_prefix("/scope/") {
+            if let Ok(index) = rest.parse::<usize>() {
+                return Ok(scope_ws::handle_ws_upgrade(
+                    req,
+                    index,
+                    &state.scsynth_addr,
+                    state.buffer_streams.clone(),
+                ));
+            }
+        }
+        if let Some(rest) = path.strip_prefix("/bus/") {
+            if let Ok(index) = rest.parse::<i32>() {
+                // `?count=<n>&rate=<hz>`: buses from `index`, polls per
+                // second.
+                let count = query_param(&req, "count").and_then(|c| c.parse().ok());
+                let rate = query_param(&req, "rate").and_then(|r| r.parse().ok());
+                let spec = ControlSpec {
+                    source: ControlSource::Bus {
+                        index,
+                        count: count.unwrap_or(1),
+                    },
+                    rate: rate.unwrap_or(DEFAULT_RATE),
+                };
+                return Ok(bus_ws::handle_ws_upgrade(
+                    req,
+                    spec,
+                    &state.scsynth_addr,
+                    state.control_streams.clone(),
                 ));
             }
         }
@@ -130,6 +228,29 @@ async fn handle_request(
         return Ok(bridge_router(req, inner, &state.data_dir, plugin::router::handle).await);
     }
 
+    if path == "/status" {
+        let body = serde_json::to_vec(&state.server_info.status()).unwrap_or_default();
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .header("access-control-allow-origin", "*")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap());
+    }
+
+    if let Some(rest) = path.strip_prefix("/buffer/") {
+        return Ok(handle_buffer(req, rest, state).await);
+    }
+
+    if path == "/recordings" || path.starts_with("/recordings/") {
+        return Ok(handle_recordings(req, &path, state).await);
+    }
+
+    // Pattern sequencer: same surface as the Tauri commands.
+    if path == "/clocks" || path == "/patterns" || path.starts_with("/patterns/") {
+        return Ok(handle_patterns(req, &path, state).await);
+    }
+
     // Static asset serving with SPA fallback
     Ok(serve_asset(&path, &state.context))
 }
@@ -159,6 +280,244 @@ async fn bridge_router(
     Response::from_parts(parts, Full::new(Bytes::from(body)))
 }
 
+// --- Pattern sequencer ---
+
+#[derive(serde::Deserialize)]
+struct RunBody {
+    run: bool,
+}
+
+/// Routes:
+///   POST   /clocks              → create/update a tempo clock (body = ClockSpec)
+///   POST   /patterns            → create/update a pattern (body = PatternSpec)
+///   DELETE /patterns/{id}       → remove a pattern
+///   POST   /patterns/{id}/run   → start/stop a pattern (body = `{"run": bool}`)
+async fn handle_patterns(
+    req: Request<Incoming>,
+    path: &str,
+    state: &AppState,
+) -> Response<Full<Bytes>> {
+    let method = req.method().clone();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let body = match req.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(_) => Bytes::new(),
+    };
+
+    let result = match (&method, segments.as_slice()) {
+        (&Method::POST, ["clocks"]) => match serde_json::from_slice::<ClockSpec>(&body) {
+            Ok(spec) => state.patterns.clock_upsert(spec).await,
+            Err(e) => Err(format!("Invalid clock spec: {e}")),
+        },
+        (&Method::POST, ["patterns"]) => match serde_json::from_slice::<PatternSpec>(&body) {
+            Ok(spec) => state.patterns.pattern_upsert(spec, &state.scsynth_addr).await,
+            Err(e) => Err(format!("Invalid pattern spec: {e}")),
+        },
+        (&Method::DELETE, ["patterns", id]) => state.patterns.pattern_remove(id).await,
+        (&Method::POST, ["patterns", id, "run"]) => match serde_json::from_slice::<RunBody>(&body) {
+            Ok(b) => state.patterns.pattern_set_run(id, b.run).await,
+            Err(e) => Err(format!("Invalid run body: {e}")),
+        },
+        _ => return json_error(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    match result {
+        Ok(()) => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header("access-control-allow-origin", "*")
+            .body(Full::new(Bytes::new()))
+            .unwrap(),
+        Err(e) => json_error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+// --- Buffer recordings ---
+
+/// Routes:
+///   POST   /recordings          → start recording (body = RecordingSpec) → `{id, path}`
+///   DELETE /recordings/{id}     → stop and finalise → `{path}`
+async fn handle_recordings(
+    req: Request<Incoming>,
+    path: &str,
+    state: &AppState,
+) -> Response<Full<Bytes>> {
+    let method = req.method().clone();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let body = match req.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(_) => Bytes::new(),
+    };
+
+    let result = match (&method, segments.as_slice()) {
+        (&Method::POST, ["recordings"]) => match serde_json::from_slice::<RecordingSpec>(&body) {
+            Ok(spec) => {
+                let sample_rate = state
+                    .server_info
+                    .sample_rate(&state.scsynth_addr)
+                    .unwrap_or(spec.sample_rate);
+                state
+                    .recordings
+                    .start(
+                        spec,
+                        &state.data_dir,
+                        &state.scsynth_addr,
+                        sample_rate,
+                        &state.clock,
+                        &state.buffer_streams,
+                    )
+                    .await
+                    .map(|info| serde_json::json!(info))
+            }
+            Err(e) => Err(format!("Invalid recording spec: {e}")),
+        },
+        (&Method::DELETE, ["recordings", id]) => match id.parse() {
+            Ok(id) => state
+                .recordings
+                .stop(id, &state.buffer_streams)
+                .await
+                .map(|path| serde_json::json!({ "path": path })),
+            Err(_) => Err(format!("Invalid recording id \"{id}\"")),
+        },
+        _ => return json_error(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    match result {
+        Ok(body) => Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .header("access-control-allow-origin", "*")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap(),
+        Err(e) => json_error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+// --- Buffer transfer ---
+
+/// Largest upload body accepted; the whole file is held in memory while it
+/// is decoded. Ten minutes of 96 kHz stereo 32-bit float fits.
+const MAX_UPLOAD_BYTES: usize = 512 << 20;
+
+/// Routes:
+///   PUT /buffer/{bufnum}      → decode the body (WAV, AIFF, FLAC) into the
+///                               buffer at the server's rate; returns UploadInfo
+///                               (413 past `MAX_UPLOAD_BYTES`)
+///   GET /buffer/{bufnum}.wav  → the buffer's current contents as a WAV file
+async fn handle_buffer(
+    req: Request<Incoming>,
+    rest: &str,
+    state: &AppState,
+) -> Response<Full<Bytes>> {
+    if let Some(bufnum) = rest
+        .strip_suffix(".wav")
+        .and_then(|b| b.parse::<i32>().ok())
+    {
+        if req.method() != Method::GET {
+            return json_error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+        }
+        return match download::download(&state.scsynth_addr, bufnum).await {
+            Ok((_, wav)) => Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "audio/wav")
+                .header(
+                    "content-disposition",
+                    format!("attachment; filename=\"buffer-{bufnum}.wav\""),
+                )
+                .header("access-control-allow-origin", "*")
+                .body(Full::new(Bytes::from(wav)))
+                .unwrap(),
+            Err(e) => json_error(StatusCode::BAD_GATEWAY, &e),
+        };
+    }
+    let Ok(bufnum) = rest.parse::<i32>() else {
+        return json_error(StatusCode::NOT_FOUND, "Not found");
+    };
+    if req.method() != Method::PUT {
+        return json_error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    }
+    let extension = req
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .and_then(audio_extension)
+        .map(str::to_string);
+    let body = match Limited::new(req.into_body(), MAX_UPLOAD_BYTES)
+        .collect()
+        .await
+    {
+        Ok(collected) => collected.to_bytes().to_vec(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("Upload exceeds {} MiB", MAX_UPLOAD_BYTES >> 20),
+            )
+        }
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    // No client to stream progress to; log each quarter instead.
+    let mut logged = 0;
+    let result = upload::upload(
+        &state.scsynth_addr,
+        bufnum,
+        body,
+        extension,
+        state.server_info.sample_rate(&state.scsynth_addr),
+        |p| {
+            let quarter = 4 * p.sent / p.total.max(1);
+            if quarter > logged {
+                logged = quarter;
+                eprintln!("upload[buf {bufnum}] {}/{} frames", p.sent, p.total);
+            }
+        },
+    )
+    .await;
+
+    match result {
+        Ok(info) => Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .header("access-control-allow-origin", "*")
+            .body(Full::new(Bytes::from(
+                serde_json::to_vec(&info).unwrap_or_default(),
+            )))
+            .unwrap(),
+        Err(e) => json_error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+/// Container hint from an upload's `Content-Type`; the decoder sniffs the
+/// bytes anyway, so unknown types just go without.
+fn audio_extension(content_type: &str) -> Option<&'static str> {
+    match content_type.split(';').next()?.trim() {
+        "audio/wav" | "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => Some("wav"),
+        "audio/flac" | "audio/x-flac" => Some("flac"),
+        "audio/aiff" | "audio/x-aiff" => Some("aiff"),
+        _ => None,
+    }
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let body = serde_json::json!({ "error": message });
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .header("access-control-allow-origin", "*")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Value of `key` in the request's query string. Clock ids are plain names,
+/// so no percent-decoding.
+fn query_param<B>(req: &Request<B>, key: &str) -> Option<String> {
+    req.uri()
+        .query()?
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.to_string())
+}
+
 // --- Static asset serving ---
 
 fn resolve_asset(path: &str, context: &tauri::Context) -> Option<Vec<u8>> {
@@ -0,0 +1,102 @@
+use super::buffer_ws;
+use crate::ipc::buffer::{BufferStreamState, WsSink};
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+/// `/scope/{index}`: `ScopeOut2` scope buffer `index` read from scsynth's
+/// shared memory. No config frame; the server opens with one text frame
+/// (`{"subscription": {"id", "port", "index", "channels"}}` or `{"error"}`),
+/// then sends one binary frame per scope window in the buffer stream's
+/// framing.
+pub fn handle_ws_upgrade(
+    req: Request<Incoming>,
+    index: usize,
+    scsynth_addr: &str,
+    state: Arc<BufferStreamState>,
+) -> Response<Full<Bytes>> {
+    let key = match req.headers().get("sec-websocket-key") {
+        Some(k) => k.as_bytes().to_vec(),
+        None => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("content-type", "text/plain")
+                .body(Full::new(Bytes::from("Missing Sec-WebSocket-Key")))
+                .unwrap()
+        }
+    };
+
+    let accept = tokio_tungstenite::tungstenite::handshake::derive_accept_key(&key);
+    let addr = scsynth_addr.to_string();
+
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(upgraded) => handle_ws_connection(upgraded, index, addr, state).await,
+            Err(e) => eprintln!("Scope WS upgrade error: {e}"),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .header("connection", "Upgrade")
+        .header("sec-websocket-accept", accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+async fn handle_ws_connection(
+    upgraded: hyper::upgrade::Upgraded,
+    index: usize,
+    scsynth_addr: String,
+    state: Arc<BufferStreamState>,
+) {
+    let ws = tokio_tungstenite::WebSocketStream::from_raw_socket(
+        TokioIo::new(upgraded),
+        tokio_tungstenite::tungstenite::protocol::Role::Server,
+        None,
+    )
+    .await;
+
+    let (mut ws_sink, mut ws_stream) = ws.split();
+
+    let (tx, mut rx) = mpsc::channel::<Message>(4);
+    let sub = state
+        .subscribe_scope(index, &scsynth_addr, Box::new(WsSink::new(tx)))
+        .await
+        .map(|sub| (sub.id, sub));
+    let Some(sub_id) = buffer_ws::send_subscription(&mut ws_sink, sub).await else {
+        return;
+    };
+
+    // Forward scope windows to the WS client.
+    let mut pump = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if ws_sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Drain inbound frames; exit when the client closes.
+    let mut drain = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if matches!(msg, Message::Close(_)) {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut pump => drain.abort(),
+        _ = &mut drain => pump.abort(),
+    }
+
+    state.unsubscribe(sub_id).await;
+}
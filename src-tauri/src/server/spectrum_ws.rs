@@ -0,0 +1,131 @@
+use super::buffer_ws;
+use crate::clock::ClockService;
+use crate::ipc::buffer::{BufferStreamState, WsSink};
+use crate::ipc::spectrum::SpectrumSpec;
+use crate::server_info::ServerInfoService;
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+/// `/spectrum/{bufnum}`: the client's first frame is a `SpectrumSpec` as
+/// JSON text; every frame after that is one spectrum in the buffer stream's
+/// binary framing, `[bins u32 LE][f32 LE × bins]` per channel.
+pub fn handle_ws_upgrade(
+    req: Request<Incoming>,
+    bufnum: i32,
+    scsynth_addr: &str,
+    state: Arc<BufferStreamState>,
+    clock: Arc<ClockService>,
+    server_info: Arc<ServerInfoService>,
+) -> Response<Full<Bytes>> {
+    let key = match req.headers().get("sec-websocket-key") {
+        Some(k) => k.as_bytes().to_vec(),
+        None => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("content-type", "text/plain")
+                .body(Full::new(Bytes::from("Missing Sec-WebSocket-Key")))
+                .unwrap()
+        }
+    };
+
+    let accept = tokio_tungstenite::tungstenite::handshake::derive_accept_key(&key);
+    let addr = scsynth_addr.to_string();
+
+    tokio::spawn(async move {
+        match hyper::upgrade::on(req).await {
+            Ok(upgraded) => {
+                handle_ws_connection(upgraded, bufnum, addr, state, clock, server_info).await
+            }
+            Err(e) => eprintln!("Spectrum WS upgrade error: {e}"),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .header("connection", "Upgrade")
+        .header("sec-websocket-accept", accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+async fn handle_ws_connection(
+    upgraded: hyper::upgrade::Upgraded,
+    bufnum: i32,
+    scsynth_addr: String,
+    state: Arc<BufferStreamState>,
+    clock: Arc<ClockService>,
+    server_info: Arc<ServerInfoService>,
+) {
+    let ws = tokio_tungstenite::WebSocketStream::from_raw_socket(
+        TokioIo::new(upgraded),
+        tokio_tungstenite::tungstenite::protocol::Role::Server,
+        None,
+    )
+    .await;
+
+    let (mut ws_sink, mut ws_stream) = ws.split();
+
+    let spec = match ws_stream.next().await {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<SpectrumSpec>(&text) {
+            Ok(spec) => spec,
+            Err(e) => {
+                eprintln!("Spectrum WS: invalid spec: {e}");
+                return;
+            }
+        },
+        _ => return,
+    };
+    if spec.bufnum != bufnum {
+        eprintln!(
+            "Spectrum WS: bufnum mismatch (url {bufnum}, spec {})",
+            spec.bufnum
+        );
+        return;
+    }
+    let sample_rate = server_info
+        .sample_rate(&scsynth_addr)
+        .unwrap_or(spec.sample_rate);
+
+    let (tx, mut rx) = mpsc::channel::<Message>(4);
+    let sink = Box::new(WsSink::new(tx));
+    let sub = spec
+        .subscribe(sink, &scsynth_addr, sample_rate, &clock, &state)
+        .await
+        .map(|s| (s.id, s));
+    let Some(sub_id) = buffer_ws::send_subscription(&mut ws_sink, sub).await else {
+        return;
+    };
+
+    // Forward spectra from the reader to the WS client.
+    let mut pump = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if ws_sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Drain inbound frames; exit when the client closes.
+    let mut drain = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if matches!(msg, Message::Close(_)) {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut pump => drain.abort(),
+        _ = &mut drain => pump.abort(),
+    }
+
+    state.unsubscribe(sub_id).await;
+}
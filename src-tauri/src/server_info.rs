@@ -0,0 +1,286 @@
+//! scsynth server info from `/status` and `/version`.
+//!
+//! Polls `/status` twice a second from a dedicated UDP socket and keeps the
+//! last parsed `/status.reply` — nominal and actual sample rate, CPU load,
+//! node and synthdef counts — plus the `/version.reply` string. Replies are
+//! unicast back to the sender, so no `/notify` is needed.
+//!
+//! The measured rate replaces the hardcoded 48 kHz: when a `ClockService`
+//! is handed to `start()`, every clock listening on this server is
+//! restarted with the server's nominal rate on first contact and again
+//! whenever scsynth comes back after going silent (a restart also drops
+//! `/notify` registrations, which the clock restart re-sends). Buffer
+//! readers ask `sample_rate(addr)` at subscribe time, which only answers
+//! for the server being polled.
+
+use crate::clock::ClockService;
+use crate::ipc::udp;
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+const POLL_MS: u64 = 500;
+
+/// No `/status.reply` for this long means scsynth is gone; the next reply
+/// after that is treated as a (re)start.
+const OFFLINE_MS: u64 = 2000;
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub ugens: i32,
+    pub synths: i32,
+    pub groups: i32,
+    pub synthdefs: i32,
+    pub avg_cpu: f32,
+    pub peak_cpu: f32,
+    pub nominal_sample_rate: f64,
+    pub actual_sample_rate: f64,
+    /// e.g. `"scsynth 3.13.0"`; `None` until `/version.reply` arrives.
+    pub version: Option<String>,
+}
+
+impl ServerStatus {
+    /// Nominal rate as an integer, the unit `ClockService` and buffer
+    /// readers work in.
+    pub fn sample_rate(&self) -> i32 {
+        self.nominal_sample_rate.round() as i32
+    }
+}
+
+pub struct ServerInfoService {
+    /// `None` while scsynth hasn't replied (yet, or for `OFFLINE_MS`).
+    status: watch::Sender<Option<ServerStatus>>,
+    /// Address `start()` was given; `None` while stopped.
+    polled: std::sync::Mutex<Option<String>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ServerInfoService {
+    pub fn new() -> Self {
+        Self {
+            status: watch::Sender::new(None),
+            polled: std::sync::Mutex::new(None),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// (Re)bind to `scsynth_addr` and start polling. If `clock` is given,
+    /// its clocks on `scsynth_addr` are restarted with the server's sample
+    /// rate on every (re)connect.
+    pub async fn start(
+        &self,
+        scsynth_addr: &str,
+        clock: Option<Arc<ClockService>>,
+    ) -> Result<(), String> {
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+        self.status.send_replace(None);
+        *self.polled.lock().unwrap() = None;
+
+        let sock = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| format!("server info bind failed: {e}"))?;
+        sock.connect(scsynth_addr)
+            .await
+            .map_err(|e| format!("server info connect {scsynth_addr} failed: {e}"))?;
+        eprintln!("server[info] polling /status on {scsynth_addr}");
+
+        let tx = self.status.clone();
+        let addr = scsynth_addr.to_string();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(POLL_MS));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut buf = [0u8; 4096];
+            let mut last_reply: Option<Instant> = None;
+            let mut version: Option<String> = None;
+            // Rate the clocks were last started with; `None` forces a restart.
+            let mut clock_sr: Option<i32> = None;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        send(&sock, "/status").await;
+                        let stale = last_reply
+                            .is_some_and(|t| t.elapsed() > Duration::from_millis(OFFLINE_MS));
+                        if stale {
+                            eprintln!("server[info] scsynth stopped responding");
+                            last_reply = None;
+                            clock_sr = None;
+                            version = None;
+                            tx.send_replace(None);
+                        }
+                    }
+                    r = sock.recv(&mut buf) => {
+                        let n = match r {
+                            Ok(n) => n,
+                            Err(e) if udp::transient(&e) => continue,
+                            Err(e) => {
+                                eprintln!("server[info] recv failed: {e}");
+                                break;
+                            }
+                        };
+                        let Ok((_, packet)) = decoder::decode_udp(&buf[..n]) else { continue };
+                        let OscPacket::Message(m) = packet else { continue };
+                        match m.addr.as_str() {
+                            "/status.reply" => {
+                                let Some(mut status) = parse_status_reply(&m) else { continue };
+                                if last_reply.is_none() {
+                                    eprintln!(
+                                        "server[info] scsynth up; sr={} actual={:.2}",
+                                        status.nominal_sample_rate, status.actual_sample_rate
+                                    );
+                                    send(&sock, "/version").await;
+                                }
+                                last_reply = Some(Instant::now());
+                                let sr = status.sample_rate();
+                                if sr > 0 && clock_sr != Some(sr) {
+                                    if let Some(c) = &clock {
+                                        c.restart_on(&addr, sr).await;
+                                    }
+                                    clock_sr = Some(sr);
+                                }
+                                status.version = version.clone();
+                                tx.send_replace(Some(status));
+                            }
+                            "/version.reply" => {
+                                version = parse_version_reply(&m);
+                                let v = version.clone();
+                                tx.send_modify(|s| {
+                                    if let Some(s) = s {
+                                        s.version = v;
+                                    }
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            eprintln!("server[info] listener exited");
+        });
+        *self.task.lock().await = Some(handle);
+        *self.polled.lock().unwrap() = Some(scsynth_addr.to_string());
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+        }
+        self.status.send_replace(None);
+        *self.polled.lock().unwrap() = None;
+    }
+
+    /// Last `/status.reply`, or `None` if scsynth isn't answering.
+    pub fn status(&self) -> Option<ServerStatus> {
+        self.status.borrow().clone()
+    }
+
+    /// The polled server's nominal rate, if `scsynth_addr` is the server
+    /// being polled and it is answering. Callers on any other server keep
+    /// their own rate.
+    pub fn sample_rate(&self, scsynth_addr: &str) -> Option<i32> {
+        if self.polled.lock().unwrap().as_deref() != Some(scsynth_addr) {
+            return None;
+        }
+        self.status
+            .borrow()
+            .as_ref()
+            .map(|s| s.sample_rate())
+            .filter(|sr| *sr > 0)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<ServerStatus>> {
+        self.status.subscribe()
+    }
+}
+
+/// One-off `/status` for callers that need the server's state once rather
+/// than a poller, e.g. the headless CLI commands. Retries every `POLL_MS`
+/// until `timeout`.
+pub async fn query_status(scsynth_addr: &str, timeout: Duration) -> Result<ServerStatus, String> {
+    let sock = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("server info bind failed: {e}"))?;
+    sock.connect(scsynth_addr)
+        .await
+        .map_err(|e| format!("server info connect {scsynth_addr} failed: {e}"))?;
+    let query = async {
+        let mut interval = tokio::time::interval(Duration::from_millis(POLL_MS));
+        let mut buf = [0u8; 4096];
+        loop {
+            tokio::select! {
+                _ = interval.tick() => send(&sock, "/status").await,
+                r = sock.recv(&mut buf) => {
+                    let Ok(n) = r else { continue };
+                    let Ok((_, OscPacket::Message(m))) = decoder::decode_udp(&buf[..n]) else { continue };
+                    if m.addr == "/status.reply" {
+                        if let Some(status) = parse_status_reply(&m) {
+                            return status;
+                        }
+                    }
+                }
+            }
+        }
+    };
+    tokio::time::timeout(timeout, query)
+        .await
+        .map_err(|_| format!("no /status.reply from {scsynth_addr}"))
+}
+
+async fn send(sock: &UdpSocket, addr: &str) {
+    let msg = OscMessage {
+        addr: addr.into(),
+        args: vec![],
+    };
+    if let Ok(bytes) = encoder::encode(&OscPacket::Message(msg)) {
+        let _ = sock.send(&bytes).await;
+    }
+}
+
+fn num(arg: Option<&OscType>) -> Option<f64> {
+    match arg? {
+        OscType::Int(i) => Some(*i as f64),
+        OscType::Long(l) => Some(*l as f64),
+        OscType::Float(f) => Some(*f as f64),
+        OscType::Double(d) => Some(*d),
+        _ => None,
+    }
+}
+
+/// `/status.reply 1 ugens synths groups synthdefs avgCPU peakCPU
+/// nominalSR actualSR`
+fn parse_status_reply(m: &OscMessage) -> Option<ServerStatus> {
+    let a = &m.args;
+    Some(ServerStatus {
+        ugens: num(a.get(1))? as i32,
+        synths: num(a.get(2))? as i32,
+        groups: num(a.get(3))? as i32,
+        synthdefs: num(a.get(4))? as i32,
+        avg_cpu: num(a.get(5))? as f32,
+        peak_cpu: num(a.get(6))? as f32,
+        nominal_sample_rate: num(a.get(7))?,
+        actual_sample_rate: num(a.get(8))?,
+        version: None,
+    })
+}
+
+/// `/version.reply name major minor patch branch commit`
+fn parse_version_reply(m: &OscMessage) -> Option<String> {
+    let mut it = m.args.iter();
+    let name = match it.next()? {
+        OscType::String(s) => s.clone(),
+        _ => return None,
+    };
+    let major = num(it.next())? as i32;
+    let minor = num(it.next())? as i32;
+    let patch = match it.next() {
+        Some(OscType::String(s)) => s.clone(),
+        _ => String::new(),
+    };
+    Some(format!("{name} {major}.{minor}{patch}"))
+}
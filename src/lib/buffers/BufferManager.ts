@@ -10,9 +10,30 @@ import {isBuffer} from '@/lib/utils/guards';
 
 export type BufferStream = SampleStream;
 
+/** Scope-style triggering (src-tauri/src/ipc/trigger.rs): batches become
+ *  fixed `frames`-long windows with the edge `preTrigger` frames in. */
+export interface TriggerConfig {
+    /** `'auto'` (default) free-runs when nothing triggers; `'single'`
+     *  captures one window. */
+    mode?: 'auto' | 'normal' | 'single';
+    channel?: number;
+    level?: number;
+    slope?: 'rising' | 'falling';
+    /** Default 0.01. */
+    hysteresis?: number;
+    frames: number;
+    /** Default half the window. */
+    preTrigger?: number;
+    /** Minimum frames between edges. */
+    holdoff?: number;
+}
+
 export interface BufferStreamConfig {
     bufnum: number;
     frames: number;
+    /** Buffer channel count (default 1). Batches arrive planar: `chunk`
+     *  samples of channel 0, then channel 1, … */
+    channels?: number;
     chunk: number;
     sampleRate: number;
     scsynthAddr: string;
@@ -22,6 +43,15 @@ export interface BufferStreamConfig {
      *  Plain `sc-buffer + RecordBuf` writers (sc-scope, sc-waveform) leave
      *  this `false`/omitted. */
     phaseTracked?: boolean;
+    /** `'peak'` has the server reduce each bucket of
+     *  `sampleRate / pointsPerSecond` frames to min, max and rms. Batches
+     *  then hold three planes per channel (min, max, rms), one value per
+     *  bucket, flagged `FRAME_PEAK`. Default `'raw'`: every sample. */
+    mode?: 'raw' | 'peak';
+    /** Peak buckets per second (server default 300). */
+    pointsPerSecond?: number;
+    /** Raw mode only. */
+    trigger?: TriggerConfig;
 }
 
 /**
@@ -31,19 +61,26 @@ export interface BufferStreamConfig {
  */
 export function createBufferStream(cfg: BufferStreamConfig): SampleStream {
     const phaseTracked = cfg.phaseTracked ?? false;
+    const channels = cfg.channels ?? 1;
+    const mode = cfg.mode ?? 'raw';
     const adapter: SampleStreamAdapter = IS_TAURI
         ? new TauriSampleStreamAdapter({
             start: async (channel) => {
                 const {invoke} = await import('@tauri-apps/api/core');
-                return invoke<number>('buffer_subscribe', {
+                const sub = await invoke<{id: number}>('buffer_subscribe', {
                     bufnum: cfg.bufnum,
                     frames: cfg.frames,
+                    channels,
                     chunk: cfg.chunk,
                     sampleRate: cfg.sampleRate,
                     scsynthAddr: cfg.scsynthAddr,
                     phaseTracked,
+                    mode,
+                    pointsPerSecond: cfg.pointsPerSecond,
+                    trigger: cfg.trigger,
                     channel,
                 });
+                return sub.id;
             },
             stop: async (handle) => {
                 const {invoke} = await import('@tauri-apps/api/core');
@@ -53,15 +90,34 @@ export function createBufferStream(cfg: BufferStreamConfig): SampleStream {
         : new WebSocketSampleStreamAdapter({
             path: `/buffer/${cfg.bufnum}`,
             onOpen: (ws) => {
-                // Header layout matches server/buffer_ws.rs: 20 bytes, all i32
-                // LE — bufnum, chunk, frames, sampleRate, phaseTracked (0/1).
-                const header = new ArrayBuffer(20);
+                // Header layout matches server/buffer_ws.rs: 64 bytes, i32 LE
+                // unless noted — bufnum, chunk, frames, sampleRate,
+                // phaseTracked (0/1), channels, mode (0 raw / 1 peak),
+                // pointsPerSecond (0 = default), then the trigger: mode
+                // (0 off / 1 auto / 2 normal / 3 single), channel, level
+                // (f32), hysteresis (f32), slope (0 rising / 1 falling),
+                // frames, preTrigger (-1 = half), holdoff.
+                const header = new ArrayBuffer(64);
                 const view = new DataView(header);
                 view.setInt32(0, cfg.bufnum, true);
                 view.setInt32(4, cfg.chunk, true);
                 view.setInt32(8, cfg.frames, true);
                 view.setInt32(12, cfg.sampleRate, true);
                 view.setInt32(16, phaseTracked ? 1 : 0, true);
+                view.setInt32(20, channels, true);
+                view.setInt32(24, mode === 'peak' ? 1 : 0, true);
+                view.setInt32(28, cfg.pointsPerSecond ?? 0, true);
+                const t = cfg.trigger;
+                if (t) {
+                    view.setInt32(32, {auto: 1, normal: 2, single: 3}[t.mode ?? 'auto'], true);
+                    view.setInt32(36, t.channel ?? 0, true);
+                    view.setFloat32(40, t.level ?? 0, true);
+                    view.setFloat32(44, t.hysteresis ?? 0.01, true);
+                    view.setInt32(48, t.slope === 'falling' ? 1 : 0, true);
+                    view.setInt32(52, t.frames, true);
+                    view.setInt32(56, t.preTrigger ?? -1, true);
+                    view.setInt32(60, t.holdoff ?? 0, true);
+                }
                 ws.send(header);
             },
         });
@@ -93,6 +149,25 @@ export class BufferManager {
         const cached = this.streams.get(id);
         if (cached) return cached;
 
+        const cfg = this.configFor(id);
+        if (!cfg) return null;
+        const stream = createBufferStream(cfg);
+        this.streams.set(id, stream);
+        return stream;
+    }
+
+    /** A stream of its own on buffer `id`, neither cached nor shared, for
+     *  consumers that want another `mode` than sc-buffer's raw stream. The
+     *  caller opens and closes it. */
+    createStream(
+        id: string,
+        options: Pick<BufferStreamConfig, 'mode' | 'pointsPerSecond'>,
+    ): SampleStream | null {
+        const cfg = this.configFor(id);
+        return cfg ? createBufferStream({...cfg, ...options}) : null;
+    }
+
+    private configFor(id: string): BufferStreamConfig | null {
         const node = runtimeApi.nodes[id];
         if (!node || !isBuffer(node)) return null;
         const buf = node.runtime;
@@ -105,14 +180,13 @@ export class BufferManager {
         const chunk = Math.max(1, Math.floor(buf.frames / chunks));
 
         const {host, port} = optionsApi.scsynth;
-        const stream = createBufferStream({
+        return {
             bufnum: buf.bufnum,
             frames: buf.frames,
+            channels: Math.max(1, buf.channels),
             chunk,
             sampleRate: Math.round(sampleRate),
             scsynthAddr: `${host}:${port}`,
-        });
-        this.streams.set(id, stream);
-        return stream;
+        };
     }
 }
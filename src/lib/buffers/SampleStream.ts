@@ -1,6 +1,7 @@
 import type {Channel} from '@tauri-apps/api/core';
+import {parseFrame, type StreamFrame} from './frame';
 
-export type SampleHandler = (samples: Float32Array) => void;
+export type SampleHandler = (samples: Float32Array, frame: StreamFrame) => void;
 
 /**
  * Transport-specific implementation that produces f32 sample batches. Adapters
@@ -15,7 +16,7 @@ export interface SampleStreamAdapter {
 }
 
 export interface TauriSampleStreamSpec {
-    start: (channel: Channel<number[]>) => Promise<unknown>;
+    start: (channel: Channel<ArrayBuffer>) => Promise<unknown>;
     stop: (handle: unknown) => Promise<void>;
 }
 
@@ -26,7 +27,7 @@ export interface WebSocketSampleStreamSpec {
 
 /** Events emitted by `SampleStream`. */
 export interface SampleStreamEventMap {
-    message: [samples: Float32Array];
+    message: [samples: Float32Array, frame: StreamFrame];
 }
 
 type Listener = (...args: unknown[]) => void;
@@ -46,7 +47,7 @@ export class SampleStream {
     private _gen = 0;
 
     constructor(private adapter: SampleStreamAdapter) {
-        this.adapter.onMessages((samples) => this.emit('message', samples));
+        this.adapter.onMessages((samples, frame) => this.emit('message', samples, frame));
     }
 
     get isOpen(): boolean {
@@ -110,8 +111,11 @@ export class TauriSampleStreamAdapter implements SampleStreamAdapter {
     async open(): Promise<void> {
         const gen = ++this._gen;
         const {Channel} = await import('@tauri-apps/api/core');
-        const channel = new Channel<number[]>();
-        channel.onmessage = (samples) => this.cb(Float32Array.from(samples));
+        const channel = new Channel<ArrayBuffer>();
+        channel.onmessage = (data) => {
+            const parsed = parseFrame(data);
+            if (parsed) this.cb(...parsed);
+        };
         const handle = await this.spec.start(channel);
         if (gen !== this._gen) {
             // close() landed during the await — free the subscription we
@@ -167,12 +171,15 @@ export class WebSocketSampleStreamAdapter implements SampleStreamAdapter {
         }
         this.ws = ws;
         ws.onmessage = (ev) => {
-            const data = ev.data as ArrayBuffer;
-            if (data.byteLength < 4) return;
-            const view = new DataView(data);
-            const n = view.getUint32(0, true);
-            if (data.byteLength < 4 + n * 4) return;
-            this.cb(new Float32Array(data, 4, n));
+            // The one text frame answers the config: the reader actually
+            // used, or why the subscription was refused.
+            if (typeof ev.data === 'string') {
+                const reply = JSON.parse(ev.data) as {error?: string};
+                if (reply.error) console.warn(`${this.spec.path}: ${reply.error}`);
+                return;
+            }
+            const parsed = parseFrame(ev.data as ArrayBuffer);
+            if (parsed) this.cb(...parsed);
         };
     }
 
@@ -0,0 +1,72 @@
+import assert from 'node:assert/strict';
+import {describe, it} from 'node:test';
+import {FRAME_GAP, FRAME_PEAK, FRAME_RESUMED, mixdown, parseFrame, peaks} from './frame.ts';
+
+/** `GAP_FRAME` from src-tauri/src/ipc/buffer.rs; keep the two in step. */
+const GAP_FRAME = [
+    0x01, 0x05, 0x02, 0x00, // version, GAP | RESUMED, 2 channels
+    0x02, 0x00, 0x00, 0x00, // seq 2
+    0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // position 2^32 + 2
+    0x02, 0x00, 0x00, 0x00, // 2 frames
+    0x04, 0x00, 0x00, 0x00, // 4 dropped
+    0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x80, 0xbf, // 0.5, -1.0
+    0x00, 0x00, 0x80, 0x3e, 0x00, 0x00, 0x00, 0x40, // 0.25, 2.0
+];
+
+const buffer = (bytes: number[]) => new Uint8Array(bytes).buffer;
+
+describe('parseFrame', () => {
+    it('reads the header and planar samples', () => {
+        const parsed = parseFrame(buffer(GAP_FRAME));
+        assert.ok(parsed);
+        const [samples, frame] = parsed;
+        assert.deepEqual(frame, {
+            flags: FRAME_GAP | FRAME_RESUMED,
+            channels: 2,
+            seq: 2,
+            position: 2 ** 32 + 2,
+            frames: 2,
+            dropped: 4,
+        });
+        assert.deepEqual(Array.from(samples), [0.5, -1, 0.25, 2]);
+    });
+
+    it('rejects truncated frames and unknown versions', () => {
+        assert.equal(parseFrame(buffer(GAP_FRAME.slice(0, 23))), null);
+        assert.equal(parseFrame(buffer(GAP_FRAME.slice(0, 39))), null);
+        assert.equal(parseFrame(buffer([2, ...GAP_FRAME.slice(1)])), null);
+    });
+
+    it('mixes planes down to one trace', () => {
+        const [samples, frame] = parseFrame(buffer(GAP_FRAME))!;
+        assert.deepEqual(Array.from(mixdown(samples, frame)), [0.375, 0.5]);
+    });
+});
+
+/** A one-bucket `FRAME_PEAK` frame holding `planes`. */
+function peakFrame(planes: number[]): ArrayBuffer {
+    const data = new ArrayBuffer(24 + planes.length * 4);
+    const view = new DataView(data);
+    view.setUint8(0, 1);
+    view.setUint8(1, FRAME_PEAK);
+    view.setUint16(2, planes.length, true);
+    view.setUint32(16, 1, true);
+    planes.forEach((v, i) => view.setFloat32(24 + i * 4, v, true));
+    return data;
+}
+
+describe('peak frames', () => {
+    it('report buffer channels and fold their planes', () => {
+        const parsed = parseFrame(peakFrame([-0.5, 0.25, 0.5, -0.25, 1, 0.5]));
+        assert.ok(parsed);
+        const [samples, frame] = parsed;
+        assert.equal(frame.channels, 2);
+        const {min, max, rms} = peaks(samples, frame);
+        assert.deepEqual([min[0], max[0], rms[0]], [-0.5, 1, 0.5]);
+        assert.deepEqual(mixdown(samples, frame), rms);
+    });
+
+    it('rejects a plane count that is not a multiple of three', () => {
+        assert.equal(parseFrame(peakFrame([0, 0, 0, 0])), null);
+    });
+});
@@ -0,0 +1,99 @@
+/** Header fields of a buffer stream frame (src-tauri/src/ipc/buffer.rs,
+ *  `FrameEncoder`). */
+export interface StreamFrame {
+    /** `FRAME_*` bits. */
+    flags: number;
+    /** Buffer channels; a `FRAME_PEAK` batch has three planes for each. */
+    channels: number;
+    /** +1 per batch sent, including batches the transport dropped. */
+    seq: number;
+    /** Virtual frame index of the first frame. */
+    position: number;
+    frames: number;
+    /** Frames missing right before this batch. */
+    dropped: number;
+}
+
+export const FRAME_VERSION = 1;
+const FRAME_HEADER_LEN = 24;
+/** The transport dropped batches before this one; see `dropped`. */
+export const FRAME_GAP = 1;
+/** Zeros standing in for a paused writer. */
+export const FRAME_SILENT = 2;
+/** First real batch after a silence. */
+export const FRAME_RESUMED = 4;
+/** Zeros standing in for a read scsynth never answered. */
+export const FRAME_FILLED = 8;
+/** Auto-mode trigger window captured without a trigger. */
+export const FRAME_AUTO = 16;
+/** Peak-mode batch: min, max and rms planes per channel, one value per
+ *  bucket. */
+export const FRAME_PEAK = 32;
+
+/** Split a binary frame into its header and planar samples. `null` for
+ *  frames that are truncated or of an unknown version. The header counts
+ *  planes; a `FRAME_PEAK` frame reports a third of them as `channels`. */
+export function parseFrame(data: ArrayBuffer): [Float32Array, StreamFrame] | null {
+    if (data.byteLength < FRAME_HEADER_LEN) return null;
+    const view = new DataView(data);
+    if (view.getUint8(0) !== FRAME_VERSION) return null;
+    const frame: StreamFrame = {
+        flags: view.getUint8(1),
+        channels: view.getUint16(2, true),
+        seq: view.getUint32(4, true),
+        position: Number(view.getBigInt64(8, true)),
+        frames: view.getUint32(16, true),
+        dropped: view.getUint32(20, true),
+    };
+    const n = frame.frames * frame.channels;
+    if (data.byteLength < FRAME_HEADER_LEN + n * 4) return null;
+    if (frame.flags & FRAME_PEAK) {
+        if (frame.channels % 3 !== 0) return null;
+        frame.channels /= 3;
+    }
+    return [new Float32Array(data, FRAME_HEADER_LEN, n), frame];
+}
+
+/** A `FRAME_PEAK` batch folded across channels, one value per bucket. */
+export interface Peaks {
+    /** Lowest min of any channel. */
+    min: Float32Array;
+    /** Highest max of any channel. */
+    max: Float32Array;
+    /** Root mean square of the channels' rms. */
+    rms: Float32Array;
+}
+
+export function peaks(samples: Float32Array, frame: StreamFrame): Peaks {
+    const {channels, frames} = frame;
+    const plane = (c: number, k: number) =>
+        samples.subarray((c * 3 + k) * frames, (c * 3 + k + 1) * frames);
+    const min = new Float32Array(frames).fill(Infinity);
+    const max = new Float32Array(frames).fill(-Infinity);
+    const rms = new Float32Array(frames);
+    for (let c = 0; c < channels; c++) {
+        const [lo, hi, r] = [plane(c, 0), plane(c, 1), plane(c, 2)];
+        for (let i = 0; i < frames; i++) {
+            min[i] = Math.min(min[i], lo[i]);
+            max[i] = Math.max(max[i], hi[i]);
+            rms[i] += r[i] * r[i];
+        }
+    }
+    for (let i = 0; i < frames; i++) rms[i] = Math.sqrt(rms[i] / channels);
+    return {min, max, rms};
+}
+
+/** One trace for single-trace displays: a mono batch as is, otherwise the
+ *  mean of its planes. A peak batch mixes down to its rms (see `peaks`). */
+export function mixdown(samples: Float32Array, frame: StreamFrame): Float32Array {
+    if (frame.flags & FRAME_PEAK) return peaks(samples, frame).rms;
+    const {channels, frames} = frame;
+    if (channels <= 1) return samples;
+    const out = new Float32Array(frames);
+    for (let c = 0; c < channels; c++) {
+        const plane = samples.subarray(c * frames, (c + 1) * frames);
+        for (let i = 0; i < frames; i++) out[i] += plane[i];
+    }
+    for (let i = 0; i < frames; i++) out[i] /= channels;
+    return out;
+}
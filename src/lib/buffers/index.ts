@@ -1,6 +1,7 @@
 import {BufferManager} from './BufferManager';
 
 export * from './BufferManager';
+export * from './frame';
 export * from './SampleStream';
 
 /** App-wide singleton. Holds one `SampleStream` per sc-buffer node. */
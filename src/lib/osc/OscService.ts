@@ -121,32 +121,42 @@ export class OscService {
   }
 
   /** Fires once per connect, when status flips to CONNECTED (client id,
-   *  sample rate, and server version all known). Starts the Rust clock
-   *  service listener (Tauri only — serve mode owns the clock server-side)
-   *  and spawns the broadcaster synth at the head of the default group.
+   *  sample rate, and server version all known). Starts the Rust server
+   *  info poller and clock service listener, and spawns the broadcaster
+   *  synth at the head of the default group — Tauri only: serve mode owns
+   *  all three server-side.
    *
-   *  The two steps are in independent try/catch blocks so a failure in
+   *  The steps are in independent try/catch blocks so a failure in
    *  one doesn't prevent the other: starting the Rust listener without a
    *  broadcaster leaves readers in `Waiting`, but is recoverable; spawning
    *  the broadcaster without the listener still lets /tr reach the frontend
    *  (unused today, but the architectural separation is clean). */
   private async postConnect(): Promise<void> {
+    if (!IS_TAURI) return;
+    const {host, port} = optionsApi.scsynth;
+    try {
+      // Rust-side `/status` poller: its sample rate overrides the ones sent
+      // below and with buffer subscriptions, and it restarts the clock when
+      // scsynth comes back.
+      const {invoke} = await import('@tauri-apps/api/core');
+      await invoke('server_info_start', {scsynthAddr: `${host}:${port}`});
+    } catch (e) {
+      console.error('[server] server_info_start failed:', e);
+      logger.log(`server_info_start failed: ${e}`);
+    }
     console.log('[clock] postConnect: starting Rust ClockService');
-    if (IS_TAURI) {
-      try {
-        const {invoke} = await import('@tauri-apps/api/core');
-        const {host, port} = optionsApi.scsynth;
-        await invoke('clock_start', {
-          scsynthAddr: `${host}:${port}`,
-          // scsynth reports sampleRate as a double (e.g. 48000.279 — its
-          // measured rate, not the nominal). Round for i32 on the Rust side.
-          sampleRate: Math.round(rootApi.serverStatus.sampleRate),
-        });
-        console.log('[clock] Rust ClockService started');
-      } catch (e) {
-        console.error('[clock] clock_start failed:', e);
-        logger.log(`clock_start failed: ${e}`);
-      }
+    try {
+      const {invoke} = await import('@tauri-apps/api/core');
+      await invoke('clock_start', {
+        scsynthAddr: `${host}:${port}`,
+        // scsynth reports sampleRate as a double (e.g. 48000.279 — its
+        // measured rate, not the nominal). Round for i32 on the Rust side.
+        sampleRate: Math.round(rootApi.serverStatus.sampleRate),
+      });
+      console.log('[clock] Rust ClockService started');
+    } catch (e) {
+      console.error('[clock] clock_start failed:', e);
+      logger.log(`clock_start failed: ${e}`);
     }
     try {
       console.log('[clock] spawning broadcaster synth on scsynth');
@@ -2,7 +2,7 @@ import {html, css} from 'lit';
 import type {ScScopeItem} from '@/types/parsers';
 import type {RuntimeState} from '@/types/stores';
 import {isBuffer, isScope} from '@/lib/utils/guards';
-import {bufferManager, type BufferStream} from '@/lib/buffers';
+import {bufferManager, mixdown, type BufferStream, type SampleHandler} from '@/lib/buffers';
 import {ScElement} from './internal/sc-element.ts';
 
 interface ScopeState {
@@ -64,7 +64,7 @@ export class ScScope extends ScElement<ScScopeItem, ScopeState> {
     private _rafId: number | null = null;
     private _dirty = false;
 
-    private _subscription: {stream: BufferStream; handler: (samples: Float32Array) => void} | null = null;
+    private _subscription: {stream: BufferStream; handler: SampleHandler} | null = null;
 
     // Two equally-sized buffers. `_display` is what the draw loop reads and is
     // only ever whole-written between draws; `_shot` is the fill target that
@@ -158,7 +158,8 @@ export class ScScope extends ScElement<ScScopeItem, ScopeState> {
         }
         this._resetVisualState();
 
-        const handler = (samples: Float32Array) => this._onSamples(samples);
+        // Planar batches, `frame.channels` runs; drawn as one trace.
+        const handler: SampleHandler = (samples, frame) => this._onSamples(mixdown(samples, frame));
         stream.on('message', handler);
         this._subscription = {stream, handler};
     }
@@ -3,19 +3,25 @@ import type {ScWaveformItem} from '@/types/parsers';
 import type {RuntimeState} from '@/types/stores';
 import {isBuffer, isWaveform} from '@/lib/utils/guards';
 import {rootApi} from '@/lib/stores/api';
-import {bufferManager, type BufferStream} from '@/lib/buffers';
+import {bufferManager, peaks, type BufferStream, type SampleHandler} from '@/lib/buffers';
 import {ScElement} from './internal/sc-element.ts';
 
+/** Peak buckets per second requested from the server; also the finest
+ *  zoom, one bucket per pixel. */
+const POINTS_PER_SECOND = 1000;
+
 interface WaveformState {
     bufferId: string;
     ready: boolean;
+    /** The buffer's writer is running; the stream is open only then. */
+    active: boolean;
 }
 
 /**
- * In-memory waveform track: polls the bound `sc-buffer` via `/b_getn` while
- * recording and keeps every sample in a Float32Array. No file is written, no
- * download is offered — the captured samples live purely in the component
- * and are thrown away on disconnect.
+ * In-memory waveform track: streams the bound `sc-buffer` in peak mode while
+ * recording and keeps the min and max of every bucket. No file is written, no
+ * download is offered — the captured peaks live purely in the component and
+ * are thrown away on disconnect.
  */
 export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
     static properties = {
@@ -67,11 +73,13 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
     private _rafId: number | null = null;
     private _dirty = true;
 
-    private _subscription: {stream: BufferStream; handler: (samples: Float32Array) => void} | null = null;
-    private _captured: Float32Array = new Float32Array(0);
+    private _subscription: {stream: BufferStream; handler: SampleHandler} | null = null;
+    private _mins: Float32Array = new Float32Array(0);
+    private _maxs: Float32Array = new Float32Array(0);
     private _capturedLen = 0;
-    private _sampleRate = 0;
-    private _scrollSample = 0;
+    /** Buckets per second: the server's bucket is a whole number of frames. */
+    private _rate = 0;
+    private _scrollPoint = 0;
     private _autoScroll = false;
     private _zoomWindow = 0; // 0 = fall back to `this.window`
 
@@ -88,7 +96,7 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
     }
 
     getState(state: RuntimeState): WaveformState {
-        const empty: WaveformState = {bufferId: '', ready: false};
+        const empty: WaveformState = {bufferId: '', ready: false, active: false};
         const self = state.nodes[this.id];
         if (!self || !isWaveform(self)) return empty;
         const buf = state.nodes[self.runtime.targetId];
@@ -96,12 +104,17 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         return {
             bufferId: buf.id,
             ready: buf.runtime.loaded && buf.runtime.bufnum > 0,
+            active: buf.runtime.active,
         };
     }
 
     protected _onStateChange(prev: WaveformState, next: WaveformState): void {
         if (this._recording && !next.ready) {
             this._stopRecording();
+        } else if (this._subscription && next.active !== prev.active) {
+            const {stream} = this._subscription;
+            if (next.active) void stream.open();
+            else stream.close();
         }
         super._onStateChange(prev, next);
     }
@@ -128,20 +141,30 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         const sampleRate = rootApi.serverStatus.sampleRate;
         if (sampleRate <= 0) return;
 
-        const stream = bufferManager.getBuffer(s.bufferId);
+        const stream = bufferManager.createStream(s.bufferId, {
+            mode: 'peak',
+            pointsPerSecond: POINTS_PER_SECOND,
+        });
         if (!stream) return;
 
-        const initialCap = Math.max(1, Math.ceil(Math.max(60, this.window) * sampleRate));
-        this._captured = new Float32Array(initialCap);
+        const rate = sampleRate / Math.max(1, Math.floor(sampleRate / POINTS_PER_SECOND));
+        const initialCap = Math.max(1, Math.ceil(Math.max(60, this.window) * rate));
+        this._mins = new Float32Array(initialCap);
+        this._maxs = new Float32Array(initialCap);
         this._capturedLen = 0;
-        this._scrollSample = 0;
-        this._sampleRate = sampleRate;
+        this._scrollPoint = 0;
+        this._rate = rate;
         this._zoomWindow = 0;
         this._autoScroll = true;
         this._dirty = true;
 
-        const handler = (samples: Float32Array) => this._appendSamples(samples);
+        // Peak batches, folded across the buffer's channels to one envelope.
+        const handler: SampleHandler = (samples, frame) => {
+            const {min, max} = peaks(samples, frame);
+            this._appendPeaks(min, max);
+        };
         stream.on('message', handler);
+        if (s.active) void stream.open();
         this._subscription = {stream, handler};
         this._recording = true;
     }
@@ -150,10 +173,10 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         this._recording = false;
         this._autoScroll = false;
 
-        // Unsubscribe only — the stream lifecycle is owned by sc-buffer, which
-        // decides when to open/close based on writer-synth run state.
+        // The peak stream is ours, unlike sc-buffer's shared raw one.
         if (this._subscription) {
             this._subscription.stream.off('message', this._subscription.handler);
+            this._subscription.stream.close();
             this._subscription = null;
         }
         this._dirty = true;
@@ -161,21 +184,26 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
 
     // ── Live tail tick ─────────────────────────────────────────────────────
 
-    private _appendSamples(samples: Float32Array): void {
-        const needed = this._capturedLen + samples.length;
-        if (needed > this._captured.length) {
-            let cap = this._captured.length || 1;
+    private _appendPeaks(mins: Float32Array, maxs: Float32Array): void {
+        const needed = this._capturedLen + mins.length;
+        if (needed > this._mins.length) {
+            let cap = this._mins.length || 1;
             while (cap < needed) cap *= 2;
-            const grown = new Float32Array(cap);
-            grown.set(this._captured.subarray(0, this._capturedLen));
-            this._captured = grown;
+            const grow = (a: Float32Array) => {
+                const grown = new Float32Array(cap);
+                grown.set(a.subarray(0, this._capturedLen));
+                return grown;
+            };
+            this._mins = grow(this._mins);
+            this._maxs = grow(this._maxs);
         }
-        this._captured.set(samples, this._capturedLen);
-        this._capturedLen += samples.length;
+        this._mins.set(mins, this._capturedLen);
+        this._maxs.set(maxs, this._capturedLen);
+        this._capturedLen += mins.length;
 
         if (this._autoScroll) {
-            const visible = Math.round(this._effectiveWindow() * this._sampleRate);
-            this._scrollSample = Math.max(0, this._capturedLen - visible);
+            const visible = Math.round(this._effectiveWindow() * this._rate);
+            this._scrollPoint = Math.max(0, this._capturedLen - visible);
         }
         this._dirty = true;
     }
@@ -222,24 +250,22 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         ctx.fillRect(0, Math.floor(h / 2), w, 1);
 
         const len = this._capturedLen;
-        if (len === 0 || this._sampleRate <= 0) return;
-        const samples = this._captured.subarray(0, len);
+        if (len === 0 || this._rate <= 0) return;
 
-        const samplesPerCol = (this._effectiveWindow() * this._sampleRate) / w;
+        const pointsPerCol = (this._effectiveWindow() * this._rate) / w;
         const mid = h / 2;
 
         ctx.fillStyle = fg;
         for (let x = 0; x < w; x++) {
-            const s0 = Math.floor(this._scrollSample + x * samplesPerCol);
-            const s1 = Math.floor(this._scrollSample + (x + 1) * samplesPerCol);
-            if (s0 >= len) break;
-            const end = Math.min(s1, len);
-            if (end <= s0) continue;
+            const p0 = Math.floor(this._scrollPoint + x * pointsPerCol);
+            const p1 = Math.floor(this._scrollPoint + (x + 1) * pointsPerCol);
+            if (p0 >= len) break;
+            const end = Math.min(p1, len);
+            if (end <= p0) continue;
             let min = 1, max = -1;
-            for (let i = s0; i < end; i++) {
-                const v = samples[i];
-                if (v < min) min = v;
-                if (v > max) max = v;
+            for (let i = p0; i < end; i++) {
+                if (this._mins[i] < min) min = this._mins[i];
+                if (this._maxs[i] > max) max = this._maxs[i];
             }
             if (min > max) continue;
             const yMax = mid - max * (h / 2 - 1);
@@ -253,16 +279,16 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
     // ── Scroll + zoom (idle only) ─────────────────────────────────────────
 
     private _clampWindow(w: number): number {
-        if (this._sampleRate <= 0) return w;
-        const minW = this.width / this._sampleRate; // 1 sample per pixel
-        const captureWin = this._capturedLen > 0 ? this._capturedLen / this._sampleRate : this.window;
+        if (this._rate <= 0) return w;
+        const minW = this.width / this._rate; // 1 bucket per pixel
+        const captureWin = this._capturedLen > 0 ? this._capturedLen / this._rate : this.window;
         const maxW = Math.max(this.window, captureWin);
         return Math.max(minW, Math.min(maxW, w));
     }
 
     private _clampScroll(s: number): number {
-        if (this._sampleRate <= 0) return 0;
-        const visible = Math.round(this._effectiveWindow() * this._sampleRate);
+        if (this._rate <= 0) return 0;
+        const visible = Math.round(this._effectiveWindow() * this._rate);
         const max = Math.max(0, this._capturedLen - visible);
         return Math.max(0, Math.min(max, s));
     }
@@ -273,11 +299,11 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         e.preventDefault();
         this._canvas.setPointerCapture(e.pointerId);
         const startX = e.clientX;
-        const startScroll = this._scrollSample;
-        const samplesPerPx = (this._effectiveWindow() * this._sampleRate) / this.width;
+        const startScroll = this._scrollPoint;
+        const pointsPerPx = (this._effectiveWindow() * this._rate) / this.width;
         const onMove = (me: PointerEvent) => {
             const dx = me.clientX - startX;
-            this._scrollSample = this._clampScroll(startScroll - dx * samplesPerPx);
+            this._scrollPoint = this._clampScroll(startScroll - dx * pointsPerPx);
             this._dirty = true;
         };
         const onUp = (ue: PointerEvent) => {
@@ -299,15 +325,15 @@ export class ScWaveform extends ScElement<ScWaveformItem, WaveformState> {
         const cursorX = Math.max(0, Math.min(this.width, e.clientX - rect.left));
 
         const winBefore = this._effectiveWindow();
-        const sppBefore = (winBefore * this._sampleRate) / this.width;
-        const anchorSample = this._scrollSample + cursorX * sppBefore;
+        const pppBefore = (winBefore * this._rate) / this.width;
+        const anchorPoint = this._scrollPoint + cursorX * pppBefore;
 
         const factor = Math.pow(1.15, (e.deltaY || 0) / 100);
         const winAfter = this._clampWindow(winBefore * factor);
         this._zoomWindow = winAfter;
 
-        const sppAfter = (winAfter * this._sampleRate) / this.width;
-        this._scrollSample = this._clampScroll(anchorSample - cursorX * sppAfter);
+        const pppAfter = (winAfter * this._rate) / this.width;
+        this._scrollPoint = this._clampScroll(anchorPoint - cursorX * pppAfter);
         this._dirty = true;
     };
 